@@ -0,0 +1,20 @@
+// The engine on its own, no Tauri: the AI plays both sides of a short game.
+// cargo run --example engine --no-default-features
+
+use gomoku_tauri::ai_core::{Game, AI};
+
+fn main() {
+    let mut game = Game::from_board(vec![vec![0; 15]; 15], 1).expect("empty board");
+    let mut ai = AI::for_game(&game);
+
+    while game.winner().is_none() && !game.is_full() && game.stone_count() < 30 {
+        let Some((row, col)) = ai.find_move(&game, 3) else { break };
+        game.play(row, col).expect("AI moves are legal");
+    }
+
+    print!("{}", game.to_diagram());
+    match game.winner() {
+        Some(winner) => println!("Player {} wins", winner),
+        None => println!("No winner after {} moves", game.moves().len()),
+    }
+}
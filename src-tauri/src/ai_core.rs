@@ -1,364 +1,2731 @@
 // Gomoku AI Core - Simple, Fast, Works.
-// Dynamic board size support
+// Dynamic board size support, square or rectangular
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-
-const EMPTY: i8 = 0;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+mod bitboard;
+mod gravity;
+mod opening;
+mod puzzle;
+mod renju;
+mod swap2;
+mod tablebase;
+mod torus;
+
+#[cfg(test)]
+mod tests;
+
+use bitboard::Bitboard;
+use tablebase::Tablebase;
+use torus::{ring_len, wrapped_cell};
+
+pub use renju::Forbidden;
+pub use swap2::{swap2_phase, Swap2Color, Swap2Option, Swap2Phase};
+
+const EMPTY: i8 = Cell::Empty as i8;
+const BLACK: i8 = Cell::Black as i8;
+const WHITE: i8 = Cell::White as i8;
 const INF: i32 = 1_000_000;
 const WIN: i32 = 100_000;
-
-// Zobrist hashing
+// Scores beyond this are forced wins/losses rather than heuristics
+const WIN_THRESHOLD: i32 = WIN - 1000;
+const MAX_DEPTH: usize = 32;
+// Nodes between checks of the deadline and stop flag inside the tree
+const STOP_CHECK_NODES: u64 = 1024;
+// What negamax returns once the search is abandoned. Callers see stopped()
+// and throw the unfinished iteration away, so the value itself is unused.
+const ABORTED: i32 = 0;
+const VCF_DEPTH: usize = 8;
+// Attacking moves in a VCT; threes branch far more than fours
+const VCT_DEPTH: usize = 4;
+const QUIESCENCE_DEPTH: usize = 8;
+// Initial half-width of the aspiration window
+const ASPIRATION_WINDOW: i32 = 500;
+// Threat scans only need cells this close to a stone
+const NEIGHBOR_RADIUS: usize = 2;
+// Depth taken off the null-move search, and the least depth worth trying it at
+const NULL_MOVE_REDUCTION: i32 = 2;
+const NULL_MOVE_MIN_DEPTH: i32 = 3;
+// Moves searched at full depth before late ones get reduced, and the least
+// depth reductions apply at
+const LMR_FULL_MOVES: usize = 3;
+const LMR_MIN_DEPTH: i32 = 4;
+// Progressive widening: moves searched first below the root, plus two per
+// ply of remaining depth
+const WIDENING_BASE: usize = 4;
+// Two open threes or fours at once can rarely all be stopped
+const DOUBLE_THREAT_BONUS: i32 = 20000;
+// A four with an open three: blocking the four lets the three become an
+// open four, so this is all but a win
+const FOUR_THREE_BONUS: i32 = 40000;
+// Ordering points for touching the opponent's longest line
+const THREAT_PROXIMITY_BONUS: i32 = 2000;
+// Zobrist keys are generated from this, see ZobristHash::new
+const ZOBRIST_SEED: u64 = 0x676F_6D6F_6B75;
+// Game analysis: search depth per position, and how much worse than the
+// best move a played move must score to count as a blunder
+const ANALYSIS_DEPTH: usize = 4;
+const BLUNDER_MARGIN: i32 = 3000;
+// suggest_depth's cost model, measured on a release build: nodes at depth 1,
+// their growth per extra ply, and microseconds per node per board cell
+const ROOT_NODES: f64 = 20.0;
+const EFFECTIVE_BRANCHING: f64 = 3.5;
+const NODE_MICROS_PER_CELL: f64 = 0.17;
+
+// Zobrist hashing. A key is kept for the board under each symmetry that
+// maps it onto itself (8 when square, 4 otherwise), so equivalent positions
+// can share transposition table entries.
 #[derive(Clone)]
 struct ZobristHash {
     table: Vec<Vec<[u64; 2]>>,
-    hash: u64,
-    size: usize,
+    side: u64,
+    rows: usize,
+    cols: usize,
+    hashes: Vec<u64>,
 }
 
 impl ZobristHash {
-    fn new(size: usize) -> Self {
-        use std::collections::hash_map::RandomState;
-        use std::hash::{BuildHasher, Hash, Hasher};
-
-        let state = RandomState::new();
-        let mut table = vec![vec![[0u64; 2]; size]; size];
+    // Same keys in every run, so hashes can be stored and compared later
+    fn new(rows: usize, cols: usize) -> Self {
+        Self::with_seed(rows, cols, ZOBRIST_SEED)
+    }
 
-        for i in 0..size {
-            for j in 0..size {
-                for k in 0..2 {
-                    let mut hasher = state.build_hasher();
-                    (i, j, k).hash(&mut hasher);
-                    table[i][j][k] = hasher.finish();
-                }
-            }
-        }
+    fn with_seed(rows: usize, cols: usize, seed: u64) -> Self {
+        let mut state = seed;
+        let table = (0..rows)
+            .map(|_| (0..cols).map(|_| [splitmix64(&mut state), splitmix64(&mut state)]).collect())
+            .collect();
+        let side = splitmix64(&mut state);
+        let symmetries = if rows == cols { 8 } else { 4 };
 
-        ZobristHash { table, hash: 0, size }
+        ZobristHash { table, side, rows, cols, hashes: vec![0; symmetries] }
     }
 
     fn toggle(&mut self, row: usize, col: usize, player: i8) {
-        self.hash ^= self.table[row][col][(player - 1) as usize];
+        for sym in 0..self.hashes.len() {
+            let (r, c) = self.transform(sym, (row, col));
+            self.hashes[sym] ^= self.table[r][c][(player - 1) as usize];
+        }
     }
 
+    // Flipped on every move, so the key is set while player 2 is to move
+    fn toggle_side(&mut self) {
+        for hash in &mut self.hashes {
+            *hash ^= self.side;
+        }
+    }
+
+    // Key of the board as it stands
     fn get_hash(&self) -> u64 {
-        self.hash
+        self.hashes[0]
+    }
+
+    // Smallest key over the first `symmetries` symmetries, and the symmetry
+    // giving it
+    fn canonical(&self, symmetries: usize) -> (u64, usize) {
+        self.hashes.iter().take(symmetries).enumerate().map(|(sym, &hash)| (hash, sym)).min().unwrap_or((0, 0))
+    }
+
+    // Flips and 180 degrees first, then the four that need a square board
+    fn transform(&self, sym: usize, (row, col): (usize, usize)) -> (usize, usize) {
+        let (last_row, last_col) = (self.rows - 1, self.cols - 1);
+        match sym {
+            0 => (row, col),
+            1 => (row, last_col - col),
+            2 => (last_row - row, col),
+            3 => (last_row - row, last_col - col),
+            4 => (col, row),
+            5 => (col, last_row - row),
+            6 => (last_col - col, row),
+            _ => (last_col - col, last_row - row),
+        }
+    }
+
+    fn untransform(&self, sym: usize, cell: (usize, usize)) -> (usize, usize) {
+        // The two quarter turns undo each other, the rest undo themselves
+        let inverse = match sym {
+            5 => 6,
+            6 => 5,
+            sym => sym,
+        };
+        self.transform(inverse, cell)
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Nonzero, as xorshift needs
+fn random_seed() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+
+    RandomState::new().hash_one("rng") | 1
+}
+
+pub fn is_winning_score(score: i32) -> bool {
+    score >= WIN_THRESHOLD
+}
+
+pub fn is_losing_score(score: i32) -> bool {
+    score <= -WIN_THRESHOLD
+}
+
+// Won and lost scores are WIN minus the ply, counted from the root, of the
+// move that makes five, so faster wins and slower losses score higher. The
+// TT keeps them relative to the entry's own node instead, since the same
+// position can come up at different plies.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if is_winning_score(score) {
+        score + ply as i32
+    } else if is_losing_score(score) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if is_winning_score(score) {
+        score - ply as i32
+    } else if is_losing_score(score) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+// Shapes one player has on the board, each line counted once
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ThreatProfile {
+    pub fives: usize,
+    pub open_fours: usize,
+    // Fours with one way to five: one end blocked, or a gap to fill
+    pub fours: usize,
+    pub open_threes: usize,
+    // Open threes with a gap, like _X_XX_
+    pub broken_threes: usize,
+}
+
+// A board cell. Boards stay grids of these as i8, in Game and across the
+// Tauri boundary; Cell names the values, checks them on the way in, and is
+// what Game's queries about one player's stones take.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(i8)]
+pub enum Cell {
+    Empty = 0,
+    Black = 1,
+    White = 2,
+}
+
+impl Cell {
+    // Empty has no opponent and stays Empty
+    pub fn opponent(self) -> Cell {
+        match self {
+            Cell::Empty => Cell::Empty,
+            Cell::Black => Cell::White,
+            Cell::White => Cell::Black,
+        }
+    }
+}
+
+impl From<Cell> for i8 {
+    fn from(cell: Cell) -> i8 {
+        cell as i8
+    }
+}
+
+impl TryFrom<i8> for Cell {
+    // The value that isn't a cell
+    type Error = i8;
+
+    fn try_from(value: i8) -> Result<Self, i8> {
+        match value {
+            EMPTY => Ok(Cell::Empty),
+            BLACK => Ok(Cell::Black),
+            WHITE => Ok(Cell::White),
+            _ => Err(value),
+        }
+    }
+}
+
+// Cell::opponent for the i8 players the engine works with
+fn opponent(player: i8) -> i8 {
+    debug_assert!(player == BLACK || player == WHITE, "no opponent for {}", player);
+    BLACK + WHITE - player
+}
+
+// Why a board can't be searched
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum BoardError {
+    Empty,
+    // Rows of different lengths
+    Ragged,
+    InvalidCell { row: usize, col: usize, value: i8 },
+    // Diagram character other than '.', 'X' or 'O'
+    InvalidSymbol { row: usize, col: usize, symbol: char },
+    InvalidPlayer,
+    // Black moves first, so Black has as many stones as White when Black is
+    // to move and one more when White is
+    StoneImbalance,
+    AlreadyWon { winner: i8 },
+    // Handicap stone outside the board, or on a cell given twice
+    OffBoard { row: usize, col: usize },
+    Overlap { row: usize, col: usize },
+}
+
+impl std::fmt::Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BoardError::Empty => write!(f, "Board has no cells"),
+            BoardError::Ragged => write!(f, "Board rows differ in length"),
+            BoardError::InvalidCell { row, col, value } => write!(f, "Invalid cell value {} at ({}, {})", value, row, col),
+            BoardError::InvalidSymbol { row, col, symbol } => write!(f, "Invalid symbol '{}' at ({}, {})", symbol, row, col),
+            BoardError::InvalidPlayer => write!(f, "Player must be 1 or 2"),
+            BoardError::StoneImbalance => write!(f, "Stone counts don't fit the player to move"),
+            BoardError::AlreadyWon { winner } => write!(f, "Game already over, player {} has won", winner),
+            BoardError::OffBoard { row, col } => write!(f, "Handicap stone at ({}, {}) is off the board", row, col),
+            BoardError::Overlap { row, col } => write!(f, "More than one handicap stone at ({}, {})", row, col),
+        }
+    }
+}
+
+// Dimensions, cells and player are usable at all. Rectangular boards are fine.
+pub fn check_board_shape(board: &[Vec<i8>], current_player: i8) -> Result<(), BoardError> {
+    let cols = board.first().map_or(0, |row| row.len());
+    if cols == 0 {
+        return Err(BoardError::Empty);
+    }
+    if board.iter().any(|row| row.len() != cols) {
+        return Err(BoardError::Ragged);
     }
+    for (row, cells) in board.iter().enumerate() {
+        if let Some(col) = cells.iter().position(|&value| Cell::try_from(value).is_err()) {
+            return Err(BoardError::InvalidCell { row, col, value: cells[col] });
+        }
+    }
+    if !matches!(Cell::try_from(current_player), Ok(Cell::Black | Cell::White)) {
+        return Err(BoardError::InvalidPlayer);
+    }
+    Ok(())
+}
+
+// Stone counts of a board and the player to move they imply
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoardInfo {
+    pub black: usize,
+    pub white: usize,
+    pub total: usize,
+    // Row by row
+    pub occupied: Vec<(usize, usize)>,
+    // By parity as in free-style play: Black after an even count, White
+    // after an odd one
+    pub to_move: i8,
+    // Black has as many stones as White, or one more
+    pub balanced: bool,
+}
+
+pub fn board_info(board: &[Vec<i8>]) -> Result<BoardInfo, BoardError> {
+    check_board_shape(board, BLACK)?;
+    let occupied: Vec<(usize, usize)> = board
+        .iter()
+        .enumerate()
+        .flat_map(|(row, cells)| cells.iter().enumerate().filter(|&(_, &cell)| cell != EMPTY).map(move |(col, _)| (row, col)))
+        .collect();
+    let black = occupied.iter().filter(|&&(row, col)| board[row][col] == BLACK).count();
+    let white = occupied.len() - black;
+    Ok(BoardInfo {
+        black,
+        white,
+        total: occupied.len(),
+        occupied,
+        to_move: if (black + white).is_multiple_of(2) { BLACK } else { WHITE },
+        balanced: black == white || black == white + 1,
+    })
+}
+
+// check_board_shape, plus a position reachable in a normal game: stone
+// counts fit the player to move and nobody has five yet
+pub fn validate_board(board: &[Vec<i8>], current_player: i8) -> Result<(), BoardError> {
+    let game = Game::from_board(board.to_vec(), current_player)?;
+
+    let info = board_info(board)?;
+    if !info.balanced || info.to_move != current_player {
+        return Err(BoardError::StoneImbalance);
+    }
+
+    match game.five_on_board() {
+        Some(winner) => Err(BoardError::AlreadyWon { winner }),
+        None => Ok(()),
+    }
+}
+
+// Every cell where the side to move would win now, and every cell where
+// the opponent would if it were their turn
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CriticalSquares {
+    pub wins: Vec<(usize, usize)>,
+    pub losses: Vec<(usize, usize)>,
+}
+
+// An unbroken open three, _XXX_: its stones in order along the line and
+// the empty cells at either end that would make it an open four
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpenThree {
+    pub stones: Vec<(usize, usize)>,
+    pub ends: [(usize, usize); 2],
+}
+
+// Why Game::play refused a move
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MoveError {
+    OutOfBounds,
+    Occupied,
+    // Breaks Renju rules for Black
+    Forbidden,
+    // Above an empty cell in gravity mode
+    Floating,
+    // The last move already won
+    GameOver,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reason = match self {
+            MoveError::OutOfBounds => "Position is off the board",
+            MoveError::Occupied => "Cell is already taken",
+            MoveError::Forbidden => "Move is forbidden under Renju rules",
+            MoveError::Floating => "Stones drop to the lowest empty cell of a column",
+            MoveError::GameOver => "Game is already won",
+        };
+        f.write_str(reason)
+    }
+}
+
+// What a Tauri command failed with. Serialized as a "kind" tag next to the
+// variant's fields, e.g. {"kind":"InvalidPosition","row":3,"col":15}, so
+// the frontend can match on it rather than on the message.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum GomokuError {
+    // Row or column off the board
+    InvalidPosition { row: usize, col: usize },
+    InvalidBoard { reason: BoardError },
+    // A five is already on the board
+    GameOver { winner: i8 },
+    // Game::play or drop_stone refused the move
+    IllegalMove { reason: MoveError },
+    // The search had nothing to play
+    NoMove,
+    // AI::generate_puzzle ran out of attempts
+    NoPuzzle,
+    InvalidSize,
+    // Under 3, or longer than the board's longer side
+    InvalidWinLength { win_length: usize, max: usize },
+    UnknownSession,
+    // The session's game moved on while the AI was searching it
+    GameChanged,
+    NotSwap2Decision,
+    // A saved game that doesn't parse or doesn't add up
+    InvalidSave { message: String },
+    // A lock poisoned by a panic elsewhere
+    Internal { message: String },
+}
+
+impl std::fmt::Display for GomokuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GomokuError::InvalidPosition { row, col } => write!(f, "Position ({}, {}) is off the board", row, col),
+            GomokuError::InvalidBoard { reason } => write!(f, "{}", reason),
+            GomokuError::GameOver { winner } => write!(f, "Game already over, player {} has won", winner),
+            GomokuError::IllegalMove { reason } => write!(f, "{}", reason),
+            GomokuError::NoMove => write!(f, "No valid move found"),
+            GomokuError::NoPuzzle => write!(f, "No puzzle found"),
+            GomokuError::InvalidSize => write!(f, "Board size must be positive"),
+            GomokuError::InvalidWinLength { win_length, max } => write!(f, "Win length {} must be from 3 to {}", win_length, max),
+            GomokuError::UnknownSession => write!(f, "Unknown session"),
+            GomokuError::GameChanged => write!(f, "Game changed during the search"),
+            GomokuError::NotSwap2Decision => write!(f, "Not at a Swap2 decision point"),
+            GomokuError::InvalidSave { message } => write!(f, "Invalid saved game: {}", message),
+            GomokuError::Internal { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<BoardError> for GomokuError {
+    fn from(reason: BoardError) -> Self {
+        match reason {
+            BoardError::AlreadyWon { winner } => GomokuError::GameOver { winner },
+            reason => GomokuError::InvalidBoard { reason },
+        }
+    }
+}
+
+impl From<MoveError> for GomokuError {
+    fn from(reason: MoveError) -> Self {
+        GomokuError::IllegalMove { reason }
+    }
+}
+
+impl<T> From<PoisonError<T>> for GomokuError {
+    fn from(e: PoisonError<T>) -> Self {
+        GomokuError::Internal { message: e.to_string() }
+    }
+}
+
+// Everything needed to save and resume a game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameState {
+    pub board: Vec<Vec<i8>>,
+    pub current_player: i8,
+    pub rows: usize,
+    pub cols: usize,
+    // Oldest first
+    pub moves: Vec<(usize, usize)>,
 }
 
 #[derive(Clone)]
 pub struct Game {
     board: Vec<Vec<i8>>,
-    size: usize,
+    rows: usize,
+    cols: usize,
     current: i8,
     zobrist: ZobristHash,
+    allow_overline: bool,
+    renju_mode: bool,
+    // Stones drop to the bottom of their column, see gravity.rs
+    gravity: bool,
+    // Lines wrap around the edges, see torus.rs
+    wrap: bool,
+    // Stones in a row needed to win, 5 for Gomoku
+    win_length: usize,
+    // Moves made on this Game, not the stones it started with
+    history: Vec<(usize, usize)>,
+    // Line scores for evaluate and move ordering
+    patterns: PatternTable,
+    // Per-player totals behind evaluate, kept up to date by put
+    line_scores: [i32; 2],
+    threats: [i32; 2],
+    // Those of the threats that are fours
+    fours: [i32; 2],
+    // Sum of Game::centrality over each player's stones
+    centrality: [i32; 2],
+    // Faster line scans, for boards up to 64 cells a side
+    bits: Option<Bitboard>,
+    // Whether set_bitboard asked for them; on a torus bits stays None anyway
+    bitboard: bool,
+    // Stones within NEIGHBOR_RADIUS of each cell, kept up to date by place
+    neighbors: Vec<Vec<u16>>,
 }
 
 impl Game {
-    pub fn from_board(board: Vec<Vec<i8>>, current_player: i8) -> Self {
-        let size = board.len();
+    // Dimensions come from the board itself, rows x cols. Fails on boards
+    // check_board_shape rejects.
+    pub fn from_board(board: Vec<Vec<i8>>, current_player: i8) -> Result<Self, BoardError> {
+        check_board_shape(&board, current_player)?;
+        let rows = board.len();
+        let cols = board.first().map_or(0, |row| row.len());
         let mut game = Game {
-            board: vec![vec![EMPTY; size]; size],
-            size,
+            board: vec![vec![EMPTY; cols]; rows],
+            rows,
+            cols,
             current: current_player,
-            zobrist: ZobristHash::new(size),
+            zobrist: ZobristHash::new(rows, cols),
+            allow_overline: true,
+            renju_mode: false,
+            gravity: false,
+            wrap: false,
+            win_length: 5,
+            history: Vec::new(),
+            patterns: PatternTable::default(),
+            line_scores: [0; 2],
+            threats: [0; 2],
+            fours: [0; 2],
+            centrality: [0; 2],
+            bits: Bitboard::new(rows, cols),
+            bitboard: true,
+            neighbors: vec![vec![0; cols]; rows],
         };
 
-        for i in 0..size {
-            for j in 0..size {
-                game.board[i][j] = board[i][j];
-                if board[i][j] != EMPTY {
-                    game.zobrist.toggle(i, j, board[i][j]);
+        for (i, row) in board.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate().take(cols) {
+                game.place(i, j, cell);
+                if cell != EMPTY {
+                    game.zobrist.toggle(i, j, cell);
+                }
+            }
+        }
+        if current_player == WHITE {
+            game.zobrist.toggle_side();
+        }
+        game.refresh_eval();
+
+        Ok(game)
+    }
+
+    // Empty size x size board with Black's handicap stones already placed.
+    // White moves first after a handicap, Black on an empty board. The
+    // stones count as the starting position, not as moves.
+    pub fn with_handicap(size: usize, cells: &[(usize, usize)]) -> Result<Self, BoardError> {
+        let mut board = vec![vec![EMPTY; size]; size];
+        for &(row, col) in cells {
+            if row >= size || col >= size {
+                return Err(BoardError::OffBoard { row, col });
+            }
+            if board[row][col] != EMPTY {
+                return Err(BoardError::Overlap { row, col });
+            }
+            board[row][col] = BLACK;
+        }
+
+        let game = Self::from_board(board, if cells.is_empty() { BLACK } else { WHITE })?;
+        match game.five_on_board() {
+            Some(winner) => Err(BoardError::AlreadyWon { winner }),
+            None => Ok(game),
+        }
+    }
+
+    // One line per row: '.' empty, 'X' black, 'O' white. Blank lines and
+    // spaces between cells are ignored.
+    pub fn from_diagram(diagram: &str, current_player: i8) -> Result<Self, BoardError> {
+        let mut board = Vec::new();
+        for line in diagram.lines().filter(|line| !line.trim().is_empty()) {
+            let row = board.len();
+            let cells = line
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .enumerate()
+                .map(|(col, symbol)| match symbol {
+                    '.' => Ok(EMPTY),
+                    'X' | 'x' => Ok(BLACK),
+                    'O' | 'o' => Ok(WHITE),
+                    _ => Err(BoardError::InvalidSymbol { row, col, symbol }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            board.push(cells);
+        }
+        Self::from_board(board, current_player)
+    }
+
+    pub fn to_diagram(&self) -> String {
+        let mut diagram = String::new();
+        for row in &self.board {
+            diagram.extend(row.iter().map(|&cell| match cell {
+                BLACK => 'X',
+                WHITE => 'O',
+                _ => '.',
+            }));
+            diagram.push('\n');
+        }
+        diagram
+    }
+
+    pub fn from_state(state: &GameState) -> Result<Self, String> {
+        if state.board.len() != state.rows || state.board.iter().any(|row| row.len() != state.cols) {
+            return Err("Board doesn't match its dimensions".to_string());
+        }
+        if state.moves.iter().any(|&(r, c)| r >= state.rows || c >= state.cols || state.board[r][c] == EMPTY) {
+            return Err("Move list doesn't match the board".to_string());
+        }
+
+        let mut game = Game::from_board(state.board.clone(), state.current_player).map_err(|e| e.to_string())?;
+        game.history = state.moves.clone();
+        Ok(game)
+    }
+
+    pub fn to_state(&self) -> GameState {
+        GameState {
+            board: self.board.clone(),
+            current_player: self.current,
+            rows: self.rows,
+            cols: self.cols,
+            moves: self.history.clone(),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn current_player(&self) -> i8 {
+        self.current
+    }
+
+    // current_player as a Cell, Black or White
+    pub fn to_move(&self) -> Cell {
+        Cell::try_from(self.current).expect("the side to move is a player")
+    }
+
+    pub fn board(&self) -> &[Vec<i8>] {
+        &self.board
+    }
+
+    pub fn cell(&self, row: usize, col: usize) -> Cell {
+        Cell::try_from(self.board[row][col]).expect("boards hold only cell values")
+    }
+
+    // Moves played since the game was set up, oldest first
+    pub fn moves(&self) -> &[(usize, usize)] {
+        &self.history
+    }
+
+    // Latest move played on this Game, not counting stones it started with
+    pub fn last_move(&self) -> Option<(usize, usize)> {
+        self.history.last().copied()
+    }
+
+    // Place a stone for the side to move and pass the turn
+    pub fn play(&mut self, row: usize, col: usize) -> Result<(), MoveError> {
+        if row >= self.rows || col >= self.cols {
+            return Err(MoveError::OutOfBounds);
+        }
+        if self.board[row][col] != EMPTY {
+            return Err(MoveError::Occupied);
+        }
+        if self.winner().is_some() {
+            return Err(MoveError::GameOver);
+        }
+        if self.floats(row, col) {
+            return Err(MoveError::Floating);
+        }
+        if self.is_restricted(row, col) {
+            return Err(MoveError::Forbidden);
+        }
+        self.make_move(row, col);
+        Ok(())
+    }
+
+    // Would `player` complete a five by playing here? Only the lines
+    // through the cell are looked at, nothing is searched. Empty never wins.
+    pub fn is_winning_move(&self, row: usize, col: usize, player: Cell) -> Result<bool, MoveError> {
+        if row >= self.rows || col >= self.cols {
+            return Err(MoveError::OutOfBounds);
+        }
+        if self.board[row][col] != EMPTY {
+            return Err(MoveError::Occupied);
+        }
+        Ok(player != Cell::Empty && self.wins_at(row, col, player.into()))
+    }
+
+    // Take back the last move, returning where it was
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        let (row, col) = self.last_move()?;
+        self.undo_move(row, col, self.board[row][col]);
+        Some((row, col))
+    }
+
+    // Owner of a five anywhere on the board, however it got there
+    pub fn five_on_board(&self) -> Option<i8> {
+        (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| (row, col)))
+            .find(|&(row, col)| self.five_direction(row, col).is_some())
+            .map(|(row, col)| self.board[row][col])
+    }
+
+    // Player whose last move made five
+    pub fn winner(&self) -> Option<i8> {
+        let (row, col) = self.last_move()?;
+        self.check_win_at(row, col).then_some(self.board[row][col])
+    }
+
+    // Stones of the five the last move made, in order along the line
+    pub fn winning_line(&self) -> Option<Vec<(usize, usize)>> {
+        let (row, col) = self.last_move()?;
+        let (dr, dc) = self.five_direction(row, col)?;
+        let player = self.board[row][col];
+
+        let back = self.count_dir(row, col, -dr, -dc, player) as i32;
+        let len = self.run_length(row, col, dr, dc, player) as i32;
+        let (start_r, start_c) = (row as i32 - dr * back, col as i32 - dc * back);
+        (0..len).map(|k| self.cell_at(start_r + dr * k, start_c + dc * k)).collect()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.board.iter().flatten().all(|&cell| cell != EMPTY)
+    }
+
+    // Opening cell. An even side has no middle cell, so take the upper-left
+    // of the central ones; the board's symmetry makes them all equivalent.
+    pub fn center(&self) -> (usize, usize) {
+        ((self.rows - 1) / 2, (self.cols - 1) / 2)
+    }
+
+    // Where the search opens an empty board: the center, or the bottom of
+    // the middle column with gravity
+    fn first_move(&self) -> (usize, usize) {
+        let (row, col) = self.center();
+        if self.gravity { (self.rows - 1, col) } else { (row, col) }
+    }
+
+    pub fn stone_count(&self) -> usize {
+        self.board.iter().flatten().filter(|&&cell| cell != EMPTY).count()
+    }
+
+    // Use the bitboard for line scans where the board fits one, or the
+    // plain cell-by-cell scans. Both give the same results.
+    pub fn set_bitboard(&mut self, on: bool) {
+        self.bitboard = on;
+        self.bits = None;
+        if !on || self.wrap {
+            return;
+        }
+        self.bits = Bitboard::new(self.rows, self.cols);
+        if let Some(bits) = &mut self.bits {
+            for (row, cells) in self.board.iter().enumerate() {
+                for (col, &cell) in cells.iter().enumerate() {
+                    bits.set(row, col, cell);
                 }
             }
         }
+    }
+
+    // Overlines (6+) win in freestyle, but not under Renju rules
+    pub fn set_allow_overline(&mut self, allow: bool) {
+        self.allow_overline = allow;
+        // Changes what scores as five
+        self.refresh_eval();
+    }
+
+    pub fn allow_overline(&self) -> bool {
+        self.allow_overline
+    }
+
+    pub fn set_win_length(&mut self, win_length: usize) {
+        self.win_length = win_length;
+        self.refresh_eval();
+    }
+
+    pub fn set_patterns(&mut self, patterns: PatternTable) {
+        self.patterns = patterns;
+        self.refresh_eval();
+    }
+
+    pub fn win_length(&self) -> usize {
+        self.win_length
+    }
+
+    // Does a run of `count` win? "Five" is win_length throughout.
+    fn is_five(&self, count: usize) -> bool {
+        if self.allow_overline {
+            count >= self.win_length
+        } else {
+            count == self.win_length
+        }
+    }
+
+    // Stones short of a winning run
+    fn missing(&self, count: usize) -> usize {
+        self.win_length.saturating_sub(count)
+    }
 
-        game
+    fn in_bounds(&self, r: i32, c: i32) -> bool {
+        r >= 0 && r < self.rows as i32 && c >= 0 && c < self.cols as i32
     }
 
     fn make_move(&mut self, row: usize, col: usize) -> bool {
-        if row >= self.size || col >= self.size || self.board[row][col] != EMPTY {
+        if row >= self.rows || col >= self.cols || self.board[row][col] != EMPTY {
             return false;
         }
-        self.board[row][col] = self.current;
+        self.put(row, col, self.current);
         self.zobrist.toggle(row, col, self.current);
-        self.current = 3 - self.current;
+        self.zobrist.toggle_side();
+        self.current = opponent(self.current);
+        self.history.push((row, col));
         true
     }
 
-    fn undo_move(&mut self, row: usize, col: usize, player: i8) {
-        self.board[row][col] = EMPTY;
-        self.zobrist.toggle(row, col, player);
-        self.current = player;
+    // Set a cell, updating the evaluation totals for the lines through it
+    fn put(&mut self, row: usize, col: usize, cell: i8) {
+        self.adjust_eval(row, col, -1);
+        self.place(row, col, cell);
+        self.adjust_eval(row, col, 1);
     }
 
-    fn check_win(&self, row: usize, col: usize) -> bool {
-        let player = self.board[row][col];
-        if player == EMPTY {
-            return false;
+    // Set a cell without touching the evaluation, for probes undone
+    // before anything is evaluated
+    fn place(&mut self, row: usize, col: usize, cell: i8) {
+        let was_stone = self.board[row][col] != EMPTY;
+        self.board[row][col] = cell;
+        if let Some(bits) = &mut self.bits {
+            bits.set(row, col, cell);
+        }
+        match (was_stone, cell != EMPTY) {
+            (false, true) => self.count_neighbor(row, col, 1),
+            (true, false) => self.count_neighbor(row, col, -1),
+            _ => {}
+        }
+    }
+
+    // Add (sign 1) or remove (sign -1) the stone at (row, col) from the
+    // neighbor counts around it
+    fn count_neighbor(&mut self, row: usize, col: usize, sign: i32) {
+        let radius = NEIGHBOR_RADIUS as i32;
+        for dr in -radius..=radius {
+            for dc in -radius..=radius {
+                let Some((r, c)) = self.cell_at(row as i32 + dr, col as i32 + dc) else { continue };
+                if (r, c) != (row, col) {
+                    self.neighbors[r][c] = (self.neighbors[r][c] as i32 + sign) as u16;
+                }
+            }
+        }
+    }
+
+    // Neighbor counts from scratch, when what's near what changes
+    fn recount_neighbors(&mut self) {
+        self.neighbors = vec![vec![0; self.cols]; self.rows];
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] != EMPTY {
+                    self.count_neighbor(row, col, 1);
+                }
+            }
         }
+    }
 
+    // Add (sign 1) or remove (sign -1) the terms of every stone sharing a
+    // line with (row, col), along that line
+    fn adjust_eval(&mut self, row: usize, col: usize, sign: i32) {
         let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let span = self.rows.max(self.cols) as i32;
 
-        for &(dr, dc) in &dirs {
-            let count = 1
-                + self.count_dir(row, col, dr, dc, player)
-                + self.count_dir(row, col, -dr, -dc, player);
+        let cell = self.board[row][col];
+        if cell != EMPTY {
+            self.centrality[(cell - 1) as usize] += sign * self.centrality(row, col);
+        }
 
-            if count >= 5 {
-                return true;
+        for &(dr, dc) in &dirs {
+            // Once round a torus line, each cell once
+            let steps = if self.wrap { 0..=self.ring(dr, dc) as i32 - 1 } else { -span..=span };
+            for k in steps {
+                let Some((r, c)) = self.cell_at(row as i32 + dr * k, col as i32 + dc * k) else { continue };
+                let player = self.board[r][c];
+                if player == EMPTY {
+                    continue;
+                }
+                let i = (player - 1) as usize;
+                self.line_scores[i] += sign * self.eval_dir(r, c, dr, dc, player);
+                if let Some(four) = self.line_threat(r, c, dr, dc, player) {
+                    self.threats[i] += sign;
+                    if four {
+                        self.fours[i] += sign;
+                    }
+                }
             }
         }
-        false
     }
 
-    fn count_dir(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> usize {
-        let mut count = 0;
-        let (mut r, mut c) = (row as i32 + dr, col as i32 + dc);
+    fn refresh_eval(&mut self) {
+        for player in [BLACK, WHITE] {
+            let i = (player - 1) as usize;
+            self.line_scores[i] = (0..self.rows)
+                .flat_map(|row| (0..self.cols).map(move |col| (row, col)))
+                .filter(|&(row, col)| self.board[row][col] == player)
+                .map(|(row, col)| self.eval_position(row, col, player))
+                .sum();
+            let (threats, fours) = self.threat_count(player);
+            self.threats[i] = threats as i32;
+            self.fours[i] = fours as i32;
+            self.centrality[i] = (0..self.rows)
+                .flat_map(|row| (0..self.cols).map(move |col| (row, col)))
+                .filter(|&(row, col)| self.board[row][col] == player)
+                .map(|(row, col)| self.centrality(row, col))
+                .sum();
+        }
+    }
 
-        while r >= 0 && r < self.size as i32 && c >= 0 && c < self.size as i32
-              && self.board[r as usize][c as usize] == player {
-            count += 1;
-            r += dr;
-            c += dc;
+    // Rings from the edge: 0 on the edge, highest at the center. A torus
+    // has no edge.
+    fn centrality(&self, row: usize, col: usize) -> i32 {
+        if self.wrap {
+            return 0;
         }
-        count
+        let ring = |i: usize, n: usize| i.min(n - 1 - i) as i32;
+        ring(row, self.rows).min(ring(col, self.cols))
     }
 
-    fn evaluate(&self) -> i32 {
-        let current_score = self.evaluate_player(self.current);
-        let opponent_score = self.evaluate_player(3 - self.current);
-        current_score - (opponent_score as f32 * 1.1) as i32
+    // Hand the move to the other side without placing a stone
+    fn pass(&mut self) {
+        self.zobrist.toggle_side();
+        self.current = opponent(self.current);
     }
 
-    fn evaluate_player(&self, player: i8) -> i32 {
-        let mut score = 0;
-        for row in 0..self.size {
-            for col in 0..self.size {
-                if self.board[row][col] == player {
-                    score += self.eval_position(row, col, player);
+    // First cell where the side to move completes five
+    fn find_five(&mut self) -> Option<(usize, usize)> {
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] != EMPTY || !self.has_neighbor(row, col, 1) || self.floats(row, col) {
+                    continue;
+                }
+                let player = self.current;
+                self.make_move(row, col);
+                let win = self.check_win_at(row, col);
+                self.undo_move(row, col, player);
+                if win {
+                    return Some((row, col));
                 }
             }
         }
-        score
+        None
     }
 
-    fn eval_position(&self, row: usize, col: usize, player: i8) -> i32 {
-        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
-        let mut score = 0;
+    fn undo_move(&mut self, row: usize, col: usize, player: i8) {
+        self.put(row, col, EMPTY);
+        self.zobrist.toggle(row, col, player);
+        self.zobrist.toggle_side();
+        self.current = player;
+        self.history.pop();
+    }
 
-        for &(dr, dc) in &dirs {
-            let (count, open) = self.eval_line(row, col, dr, dc, player);
-            score += match (count, open) {
-                (5.., _) => WIN,
-                (4, 2) => 10000,
-                (4, 1) => 5000,
-                (3, 2) => 5000,
-                (3, 1) => 500,
-                (2, 2) => 500,
-                (2, 1) => 50,
-                _ => 10,
-            };
-        }
-        score
+    // Did the move just made at (row, col) make five? Only the lines
+    // through the last move can have changed, so only those are checked.
+    fn check_win_at(&self, row: usize, col: usize) -> bool {
+        debug_assert_eq!(self.last_move(), Some((row, col)), "win checked away from the last move");
+        self.five_direction(row, col).is_some()
     }
 
-    fn eval_line(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> (usize, usize) {
-        let mut count = 1;
-        let mut open = 0;
+    // Direction of a five through the stone at (row, col), if any
+    fn five_direction(&self, row: usize, col: usize) -> Option<(i32, i32)> {
+        let player = self.board[row][col];
+        if player == EMPTY {
+            return None;
+        }
 
-        let (mut r, mut c) = (row as i32 + dr, col as i32 + dc);
-        while r >= 0 && r < self.size as i32 && c >= 0 && c < self.size as i32 {
-            if self.board[r as usize][c as usize] == player {
-                count += 1;
-            } else if self.board[r as usize][c as usize] == EMPTY {
-                open += 1;
-                break;
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        dirs.into_iter().find(|&(dr, dc)| self.is_five(self.run_length(row, col, dr, dc, player)))
+    }
+
+    // Stones of `player` next to (row, col) going (dr, dc), stopping short
+    // of coming back round to (row, col) on a torus
+    fn count_dir(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> usize {
+        if let Some(bits) = &self.bits {
+            return bits.count_dir(row, col, dr, dc, player);
+        }
+
+        let limit = self.ring(dr, dc) - 1;
+        let mut count = 0;
+        let (mut r, mut c) = (row as i32 + dr, col as i32 + dc);
+
+        while count < limit && self.cell_at(r, c).is_some_and(|(r, c)| self.board[r][c] == player) {
+            count += 1;
+            r += dr;
+            c += dc;
+        }
+        count
+    }
+
+    // The unbroken run through (row, col) with a stone of `player` there,
+    // at most once round a torus line
+    fn run_length(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> usize {
+        let count = 1 + self.count_dir(row, col, dr, dc, player) + self.count_dir(row, col, -dr, -dc, player);
+        count.min(self.ring(dr, dc))
+    }
+
+    // Static score for the side to move, no search
+    pub fn evaluate(&self, weights: &EvalConfig) -> i32 {
+        debug_assert!(
+            [BLACK, WHITE].iter().all(|&p| self.player_score(p) == self.evaluate_player(p)),
+            "incremental evaluation out of sync"
+        );
+        let score = |player: i8| {
+            let score = self.player_score(player);
+            if player == BLACK { score - (score as f32 * weights.first_player_penalty) as i32 } else { score }
+        };
+        let current_score = score(self.current);
+        let opponent_score = score(opponent(self.current));
+        // Central stones count for more while there are few threats to
+        // play around
+        let (me, them) = ((self.current - 1) as usize, (2 - self.current) as usize);
+        let positional = (self.centrality[me] - self.centrality[them]) * weights.center_weight / (1 + self.threats[0] + self.threats[1]);
+        // Static scores stay out of the won/lost range, only search finds wins
+        (current_score - (opponent_score as f32 * weights.opponent_weight) as i32 + positional).clamp(1 - WIN_THRESHOLD, WIN_THRESHOLD - 1)
+    }
+
+    // evaluate_player from the running totals
+    pub fn player_score(&self, player: i8) -> i32 {
+        let i = (player - 1) as usize;
+        self.line_scores[i] + Self::threat_bonus(self.threats[i] as usize, self.fours[i] as usize)
+    }
+
+    // Full recompute, the reference for the running totals
+    fn evaluate_player(&self, player: i8) -> i32 {
+        let mut score = 0;
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] == player {
+                    score += self.eval_position(row, col, player);
+                }
+            }
+        }
+        let (threats, fours) = self.threat_count(player);
+        score + Self::threat_bonus(threats, fours)
+    }
+
+    // Bonus for threats that can't all be answered with one stone
+    fn threat_bonus(threats: usize, fours: usize) -> i32 {
+        if fours >= 1 && threats > fours {
+            FOUR_THREE_BONUS
+        } else if threats >= 2 {
+            DOUBLE_THREAT_BONUS
+        } else {
+            0
+        }
+    }
+
+    // Classify every line once, from its first stone. Empty has no lines.
+    pub fn threat_profile(&self, player: Cell) -> ThreatProfile {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let mut profile = ThreatProfile::default();
+        if player == Cell::Empty {
+            return profile;
+        }
+        let player = i8::from(player);
+
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] != player {
+                    continue;
+                }
+                for &(dr, dc) in &dirs {
+                    if !self.starts_line(row, col, dr, dc, player) {
+                        continue;
+                    }
+
+                    match self.eval_line(row, col, dr, dc, player).kind {
+                        ThreatKind::Five => profile.fives += 1,
+                        ThreatKind::OpenFour => profile.open_fours += 1,
+                        ThreatKind::ClosedFour => profile.fours += 1,
+                        ThreatKind::OpenThree => profile.open_threes += 1,
+                        ThreatKind::BrokenThree => profile.broken_threes += 1,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        profile
+    }
+
+    // Stones of `player`'s longest line, one gap allowed as in eval_line
+    fn longest_run(&self, player: i8) -> Vec<(usize, usize)> {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let mut best = None;
+
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] != player {
+                    continue;
+                }
+                for &(dr, dc) in &dirs {
+                    if !self.starts_line(row, col, dr, dc, player) {
+                        continue;
+                    }
+                    let count = self.eval_line(row, col, dr, dc, player).count;
+                    if best.is_none_or(|(longest, _, _)| count > longest) {
+                        best = Some((count, (row, col), (dr, dc)));
+                    }
+                }
+            }
+        }
+
+        // Walk the line from its first stone, over the gap
+        let Some((count, (row, col), (dr, dc))) = best else { return Vec::new() };
+        let mut stones = Vec::new();
+        let (mut r, mut c) = (row as i32, col as i32);
+        while let Some((cr, cc)) = self.cell_at(r, c).filter(|&(cr, cc)| stones.len() < count && self.board[cr][cc] != opponent(player)) {
+            if self.board[cr][cc] == player {
+                stones.push((cr, cc));
+            }
+            r += dr;
+            c += dc;
+        }
+        stones
+    }
+
+    // Fours and open threes, and how many of them are fours, each line
+    // counted once from its first stone
+    fn threat_count(&self, player: i8) -> (usize, usize) {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let (mut threats, mut fours) = (0, 0);
+
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] != player {
+                    continue;
+                }
+                for &(dr, dc) in &dirs {
+                    if let Some(four) = self.line_threat(row, col, dr, dc, player) {
+                        threats += 1;
+                        fours += four as usize;
+                    }
+                }
+            }
+        }
+        (threats, fours)
+    }
+
+    // A stone starts its line in direction (dr, dc) if neither the cell
+    // before it nor, across a gap, the one before that holds the player's
+    // stone. eval_line from any other stone of the line sees the same line.
+    fn starts_line(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> bool {
+        let cell = |k: i32| {
+            self.cell_at(row as i32 - k * dr, col as i32 - k * dc).map(|(r, c)| self.board[r][c])
+        };
+        let before = cell(1);
+        !(before == Some(player) || (before == Some(EMPTY) && cell(2) == Some(player)))
+    }
+
+    // Some(true) if the stone at (row, col) is the first of a four,
+    // Some(false) if of an open three
+    fn line_threat(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> Option<bool> {
+        if !self.starts_line(row, col, dr, dc, player) {
+            return None;
+        }
+        match self.eval_line(row, col, dr, dc, player).kind {
+            ThreatKind::ClosedFour | ThreatKind::OpenFour => Some(true),
+            ThreatKind::BrokenThree | ThreatKind::OpenThree => Some(false),
+            _ => None,
+        }
+    }
+
+    fn eval_position(&self, row: usize, col: usize, player: i8) -> i32 {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        dirs.iter().map(|&(dr, dc)| self.eval_dir(row, col, dr, dc, player)).sum()
+    }
+
+    // Score of the line through (row, col), counted only from its first stone
+    // so each line scores once however many stones it has
+    fn eval_dir(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> i32 {
+        if !self.starts_line(row, col, dr, dc, player) {
+            return 0;
+        }
+        self.patterns.score(self.eval_line(row, col, dr, dc, player).kind)
+    }
+
+    // Stones in line through (row, col), allowing one empty gap between
+    // them, and the shape they make
+    fn eval_line(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> Line {
+        let (count, open, gapped) = match &self.bits {
+            Some(bits) => bits.scan_line(row, col, dr, dc, player),
+            None => scan_line(&self.board, row, col, dr, dc, player, self.wrap),
+        };
+        Line { count, kind: self.shape(count, open, gapped) }
+    }
+
+    // eval_line on another board of the same size, e.g. one with a stone
+    // tried on it
+    fn eval_line_static(&self, board: &[Vec<i8>], row: usize, col: usize, dr: i32, dc: i32, player: i8) -> Line {
+        let (count, open, gapped) = scan_line(board, row, col, dr, dc, player, self.wrap);
+        Line { count, kind: self.shape(count, open, gapped) }
+    }
+
+    // Shape of a line of `count` stones with `open` free ends (2 open,
+    // 1 half-open, 0 closed), by how far it is from winning so any
+    // win_length works. A gap counts as a place to grow: _X_X_ is a split
+    // two, _XX_X_ a broken three, XX_XX a four whatever its ends.
+    fn shape(&self, count: usize, open: usize, gapped: bool) -> ThreatKind {
+        match (self.missing(count), open, gapped) {
+            (0, _, false) if self.is_five(count) => ThreatKind::Five,
+            // An overline where only exact fives win
+            (0, _, false) => ThreatKind::Other,
+            (1, 2, false) => ThreatKind::OpenFour,
+            // Filling the gap of a five-stone line is an overline, which
+            // only wins when overlines do, open ends or not
+            (0, _, true) if !self.allow_overline => ThreatKind::DeadFour,
+            (1, 1, false) | (0 | 1, _, true) => ThreatKind::ClosedFour,
+            (1, 0, false) => ThreatKind::DeadFour,
+            (2, 2, false) => ThreatKind::OpenThree,
+            (2, 2, true) => ThreatKind::BrokenThree,
+            (2, 1, _) => ThreatKind::ClosedThree,
+            (3, 2, false) => ThreatKind::OpenTwo,
+            (3, 2, true) => ThreatKind::SplitTwo,
+            (3, 1, _) => ThreatKind::ClosedTwo,
+            _ => ThreatKind::Other,
+        }
+    }
+
+    // Would `player` make five by playing here?
+    fn wins_at(&self, row: usize, col: usize, player: i8) -> bool {
+        if self.board[row][col] != EMPTY {
+            return false;
+        }
+
+        // Only the cells around the move count, so nothing needs placing
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        dirs.iter().any(|&(dr, dc)| self.is_five(self.run_length(row, col, dr, dc, player)))
+    }
+
+    // Empty cells on the lines through (row, col) where `player` would make five
+    fn winning_cells_through(&mut self, row: usize, col: usize, player: i8) -> Vec<(usize, usize)> {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let mut cells = Vec::new();
+
+        let reach = self.win_length as i32 - 1;
+        for &(dr, dc) in &dirs {
+            for k in -reach..=reach {
+                let Some((r, c)) = self.cell_at(row as i32 + dr * k, col as i32 + dc * k) else { continue };
+                if !cells.contains(&(r, c)) && !self.floats(r, c) && self.wins_at(r, c, player) {
+                    cells.push((r, c));
+                }
+            }
+        }
+        cells
+    }
+
+    // Every empty cell where `player` would make five
+    fn winning_cells(&mut self, player: i8) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.has_neighbor(row, col, NEIGHBOR_RADIUS) && !self.floats(row, col) && self.wins_at(row, col, player) {
+                    cells.push((row, col));
+                }
+            }
+        }
+        cells
+    }
+
+    pub fn critical_squares(&self) -> CriticalSquares {
+        let mut g = self.clone();
+        let me = g.current;
+        CriticalSquares { wins: g.winning_cells(me), losses: g.winning_cells(opponent(me)) }
+    }
+
+    // Every open three of `player`'s, each once, for pointing the shape out.
+    // Broken threes (_XX_X_) are left out, and Empty has none.
+    pub fn open_threes(&self, player: Cell) -> Vec<OpenThree> {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let mut threes = Vec::new();
+        if player == Cell::Empty {
+            return threes;
+        }
+        let player = i8::from(player);
+
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] != player {
+                    continue;
+                }
+                for &(dr, dc) in &dirs {
+                    if !self.starts_line(row, col, dr, dc, player) {
+                        continue;
+                    }
+                    let line = self.eval_line(row, col, dr, dc, player);
+                    if line.kind != ThreatKind::OpenThree {
+                        continue;
+                    }
+                    let (r, c) = (row as i32, col as i32);
+                    let stones = (0..line.count as i32).filter_map(|k| self.cell_at(r + dr * k, c + dc * k)).collect();
+                    let ends = [self.cell_at(r - dr, c - dc), self.cell_at(r + dr * line.count as i32, c + dc * line.count as i32)];
+                    if let [Some(before), Some(after)] = ends {
+                        threes.push(OpenThree { stones, ends: [before, after] });
+                    }
+                }
+            }
+        }
+        threes
+    }
+
+    // Did `player`'s stone at (row, col) make a four or an open three?
+    fn is_forcing(&mut self, row: usize, col: usize, player: i8) -> bool {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        !self.winning_cells_through(row, col, player).is_empty()
+            || dirs.iter().any(|&(dr, dc)| {
+                matches!(self.eval_line(row, col, dr, dc, player).kind, ThreatKind::BrokenThree | ThreatKind::OpenThree)
+            })
+    }
+
+    // Would `player`'s stone at the empty (row, col) make a four? Only the
+    // lines through it are looked at, so the neighbor counts can stay as
+    // they are.
+    fn makes_four(&mut self, row: usize, col: usize, player: i8) -> bool {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        self.board[row][col] = player;
+        if let Some(bits) = &mut self.bits {
+            bits.set(row, col, player);
+        }
+        let four = dirs
+            .iter()
+            .any(|&(dr, dc)| matches!(self.eval_line(row, col, dr, dc, player).kind, ThreatKind::ClosedFour | ThreatKind::OpenFour));
+        self.board[row][col] = EMPTY;
+        if let Some(bits) = &mut self.bits {
+            bits.set(row, col, EMPTY);
+        }
+        four
+    }
+
+    // Could `player` make a four anywhere?
+    fn can_make_four(&mut self, player: i8) -> bool {
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.board[row][col] != EMPTY || !self.has_neighbor(row, col, NEIGHBOR_RADIUS) {
+                    continue;
+                }
+                self.place(row, col, player);
+                let four = !self.winning_cells_through(row, col, player).is_empty();
+                self.place(row, col, EMPTY);
+                if four {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // Would `player` make an open four or a double four here?
+    fn open_four_at(&mut self, row: usize, col: usize, player: i8) -> bool {
+        if self.board[row][col] != EMPTY {
+            return false;
+        }
+        self.place(row, col, player);
+        let open = self.winning_cells_through(row, col, player).len() >= 2;
+        self.place(row, col, EMPTY);
+        open
+    }
+
+    // What the opponent's fours leave the side to move, whose own fives
+    // aren't looked at. Found with real moves after a pass, so the hash
+    // stays consistent.
+    pub fn defense(&self) -> Defense {
+        let mut g = self.clone();
+        let me = g.current;
+        g.pass();
+        let Some(block) = g.find_five() else { return Defense::Free };
+        if g.winning_cells(g.current).len() >= 2 {
+            return Defense::DoubleFour(block);
+        }
+        g.pass();
+
+        // A block that makes a four keeps the initiative
+        g.make_move(block.0, block.1);
+        if !g.winning_cells_through(block.0, block.1, me).is_empty() {
+            return Defense::Four(block);
+        }
+        let them = g.current;
+        for row in 0..g.rows {
+            for col in 0..g.cols {
+                if g.has_neighbor(row, col, NEIGHBOR_RADIUS) && !g.is_restricted(row, col) && g.open_four_at(row, col, them) {
+                    return Defense::FourThree(block);
+                }
+            }
+        }
+        Defense::Four(block)
+    }
+
+    // Replies to the three `player` just made at (row, col): the empty cells
+    // on its lines that leave no open four to play, or every cell on them if
+    // none does. Empty when the move made no three.
+    fn three_replies(&mut self, row: usize, col: usize, player: i8) -> Vec<(usize, usize)> {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let reach = self.win_length as i32 - 1;
+
+        let mut cells = Vec::new();
+        for &(dr, dc) in &dirs {
+            if self.missing(self.eval_line(row, col, dr, dc, player).count) > 2 {
+                continue;
+            }
+            let line: Vec<_> = (-reach..=reach)
+                .filter_map(|k| self.cell_at(row as i32 + dr * k, col as i32 + dc * k))
+                .filter(|&(r, c)| self.board[r][c] == EMPTY)
+                .collect();
+            if line.iter().any(|&(r, c)| self.open_four_at(r, c, player)) {
+                cells.extend(line);
+            }
+        }
+
+        let mut replies = Vec::new();
+        for &(r, c) in &cells {
+            self.place(r, c, opponent(player));
+            if !cells.iter().any(|&(fr, fc)| self.open_four_at(fr, fc, player)) {
+                replies.push((r, c));
+            }
+            self.place(r, c, EMPTY);
+        }
+        if replies.is_empty() { cells } else { replies }
+    }
+
+    // Neither side has a four on the board
+    fn is_quiet(&mut self) -> bool {
+        self.winning_cells(1).is_empty() && self.winning_cells(2).is_empty()
+    }
+
+    // Empty, playable and near a stone. With gravity there's one playable
+    // cell per column, so all of them are worth a look.
+    fn is_candidate(&self, row: usize, col: usize, radius: usize) -> bool {
+        self.board[row][col] == EMPTY && (self.gravity || self.has_neighbor(row, col, radius)) && !self.is_restricted(row, col)
+    }
+
+    // Candidate cells, or every playable cell when none is within `radius`
+    // of a stone (radius 0, or a sparse loaded position), so a board with
+    // room left always has moves to search
+    fn candidate_cells(&self, radius: usize) -> Vec<(usize, usize)> {
+        let cells = || (0..self.rows).flat_map(|row| (0..self.cols).map(move |col| (row, col)));
+        let near: Vec<_> = cells().filter(|&(row, col)| self.is_candidate(row, col, radius)).collect();
+        if !near.is_empty() {
+            return near;
+        }
+        cells().filter(|&(row, col)| self.board[row][col] == EMPTY && !self.is_restricted(row, col)).collect()
+    }
+
+    // How many of ZobristHash::transform's symmetries keep the rules the
+    // same. Gravity only allows the left-right flip, the others would let
+    // stones fall sideways or up.
+    fn symmetry_count(&self) -> usize {
+        if self.gravity { 2 } else { self.zobrist.hashes.len() }
+    }
+
+    // Key shared by the positions the rules can't tell apart, and the
+    // symmetry that maps this one onto it
+    fn canonical_hash(&self) -> (u64, usize) {
+        self.zobrist.canonical(self.symmetry_count())
+    }
+
+    // Symmetries, numbered as in ZobristHash::transform, that map the board
+    // onto itself, the identity first
+    fn symmetries(&self) -> Vec<usize> {
+        (0..self.symmetry_count())
+            .filter(|&sym| {
+                (0..self.rows).all(|row| {
+                    (0..self.cols).all(|col| {
+                        let (r, c) = self.zobrist.transform(sym, (row, col));
+                        self.board[r][c] == self.board[row][col]
+                    })
+                })
+            })
+            .collect()
+    }
+
+    // Any stone within `radius` of (row, col)? The usual radius is looked up
+    // in the neighbor counts rather than scanned for.
+    fn has_neighbor(&self, row: usize, col: usize, radius: usize) -> bool {
+        if radius == NEIGHBOR_RADIUS {
+            let near = self.neighbors[row][col] > 0;
+            debug_assert_eq!(near, self.scan_neighbors(row, col, radius), "neighbor count off at {:?}", (row, col));
+            return near;
+        }
+        self.scan_neighbors(row, col, radius)
+    }
+
+    fn scan_neighbors(&self, row: usize, col: usize, radius: usize) -> bool {
+        let radius = radius as i32;
+        for dr in -radius..=radius {
+            for dc in -radius..=radius {
+                if dr == 0 && dc == 0 { continue; }
+                if self.cell_at(row as i32 + dr, col as i32 + dc).is_some_and(|(r, c)| self.board[r][c] != EMPTY) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn score_move(&self, row: usize, col: usize, weights: &EvalConfig) -> i32 {
+        let mut score = 0;
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+        let mut temp_board = self.board.clone();
+        temp_board[row][col] = self.current;
+
+        let (mut fours, mut threes) = (0, 0);
+        for &(dr, dc) in &dirs {
+            let kind = self.eval_line_static(&temp_board, row, col, dr, dc, self.current).kind;
+            score += self.patterns.score(kind);
+            match kind {
+                ThreatKind::ClosedFour | ThreatKind::OpenFour => fours += 1,
+                ThreatKind::BrokenThree | ThreatKind::OpenThree => threes += 1,
+                _ => {}
+            }
+        }
+        if fours >= 1 && threes >= 1 {
+            score += FOUR_THREE_BONUS;
+        }
+
+        let mut block = 0;
+        temp_board[row][col] = opponent(self.current);
+        for &(dr, dc) in &dirs {
+            block += self.patterns.score(self.eval_line_static(&temp_board, row, col, dr, dc, opponent(self.current)).kind);
+        }
+
+        score + (block as f32 * weights.opponent_weight) as i32
+    }
+}
+
+// Shape of a line as Game::shape reads it, weakest first. Open ends are
+// free cells at both ends, closed ones a single free end.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum ThreatKind {
+    // Anything weaker, no room to grow, or an overline where only exact
+    // fives win
+    Other,
+    // A four with no cell left that makes five: both ends shut, and no gap
+    // where a stone would make exactly five
+    DeadFour,
+    ClosedTwo,
+    // Open two with a gap, _X_X_
+    SplitTwo,
+    OpenTwo,
+    ClosedThree,
+    // Open three with a gap, _XX_X_
+    BrokenThree,
+    OpenThree,
+    // One cell left to make five: one end open, or a gap whatever the ends
+    ClosedFour,
+    OpenFour,
+    Five,
+}
+
+// What eval_line found through a stone
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Line {
+    count: usize,
+    kind: ThreatKind,
+}
+
+// Line scan behind Game::eval_line: stones in line through (row, col), at
+// most one single-cell gap between them, open ends, and whether the gap
+// was used, so _XX_X_ reads as a broken three rather than a two. On a
+// torus no cell is looked at twice.
+fn scan_line(board: &[Vec<i8>], row: usize, col: usize, dr: i32, dc: i32, player: i8, wrap: bool) -> (usize, usize, bool) {
+    let (rows, cols) = (board.len(), board[0].len());
+    let cell_at = |r: i32, c: i32| wrapped_cell(rows, cols, wrap, r, c);
+    let mut left = if wrap { ring_len(rows, cols, dr, dc) - 1 } else { usize::MAX };
+    let mut count = 1;
+    let mut open = 0;
+    let mut gapped = false;
+
+    for sign in [1, -1] {
+        let (dr, dc) = (dr * sign, dc * sign);
+        let (mut r, mut c) = (row as i32 + dr, col as i32 + dc);
+        while let Some((cr, cc)) = cell_at(r, c).filter(|_| left > 0) {
+            left -= 1;
+            let cell = board[cr][cc];
+            if cell == player {
+                count += 1;
+            } else if cell == EMPTY {
+                let next = cell_at(r + dr, c + dc).filter(|_| left > 0);
+                if !gapped && next.is_some_and(|(nr, nc)| board[nr][nc] == player) {
+                    gapped = true;
+                } else {
+                    open += 1;
+                    break;
+                }
             } else {
                 break;
             }
             r += dr;
             c += dc;
         }
+    }
+
+    (count, open, gapped)
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct TTEntry {
+    depth: i32,
+    score: i32,
+    flag: TTFlag,
+    best_move: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum TTFlag {
+    Exact,
+    LowerBound,
+    UpperBound,
+}
+
+// Where a search got to, to carry on with later or in another run: the
+// deepest completed iteration with its result, and the tables the next
+// iteration would have started from. See AI::checkpoint.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchCheckpoint {
+    rows: usize,
+    cols: usize,
+    // Zobrist key of the position searched
+    position: u64,
+    pub depth: usize,
+    pub best: Option<((usize, usize), i32)>,
+    iteration_moves: Vec<(usize, usize)>,
+    entries: Vec<(u64, TTEntry)>,
+    killer_moves: Vec<[Option<(usize, usize)>; 2]>,
+    history: [Vec<Vec<i32>>; 2],
+}
+
+// A completed iteration: the position's Zobrist key, the depth and the result
+#[derive(Clone, Copy)]
+struct Completed {
+    position: u64,
+    depth: usize,
+    best: ((usize, usize), i32),
+}
+
+// A transposition table on disk, see AI::save_tt
+#[derive(Serialize, Deserialize)]
+struct TTFile {
+    rows: usize,
+    cols: usize,
+    entries: Vec<(u64, TTEntry)>,
+}
+
+// The opponent's forcing threats against the side to move, each with the
+// one cell that stops the five
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Defense {
+    // No five threatened
+    Free,
+    // A single four, the block holds
+    Four((usize, usize)),
+    // Fives on two cells, the other one wins after the block
+    DoubleFour((usize, usize)),
+    // A four and a three: after the block the three becomes an open four
+    FourThree((usize, usize)),
+}
+
+// Named strength levels, each a search depth plus an AIConfig
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Difficulty {
+    // Depth 2, no VCF, 8 candidates, a random candidate 20% of the time
+    Beginner,
+    // Depth 4, VCF but no VCT, 12 candidates at the root down to 8, no
+    // randomness
+    Intermediate,
+    // Depth 6, VCF and VCT, 20 candidates at the root down to 8, null-move
+    // pruning and LMR
+    Expert,
+}
+
+impl Difficulty {
+    pub fn depth(self) -> usize {
+        match self {
+            Difficulty::Beginner => 2,
+            Difficulty::Intermediate => 4,
+            Difficulty::Expert => 6,
+        }
+    }
+
+    pub fn config(self) -> AIConfig {
+        let base = AIConfig { opening_moves: 3, ..AIConfig::default() };
+        match self {
+            Difficulty::Beginner => AIConfig {
+                vcf: false,
+                vct: false,
+                max_candidates: 8,
+                blunder_percent: 20,
+                late_move_reductions: false,
+                ..base
+            },
+            Difficulty::Intermediate => AIConfig {
+                vct: false,
+                max_candidates: 12,
+                late_move_reductions: false,
+                ..base
+            },
+            Difficulty::Expert => AIConfig {
+                null_move: true,
+                ..base
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Decision {
+    Play((usize, usize), i32),
+    Resign,
+}
+
+// Counters from the last search
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct SearchStats {
+    pub nodes: u64,
+    pub tt_hits: u64,
+    // Deepest fully completed iteration
+    pub max_depth: usize,
+    pub elapsed_ms: u64,
+}
+
+// Reported after each completed iteration of find_move_with_progress
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SearchProgress {
+    pub depth: usize,
+    pub best_move: (usize, usize),
+    pub score: i32,
+}
+
+// A searched move in AI::trace. The score is for the player who made it,
+// and cutoff is set when it ended the search of its siblings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceNode {
+    pub mv: (usize, usize),
+    pub score: i32,
+    pub cutoff: bool,
+    pub children: Vec<TraceNode>,
+}
+
+// One move of an analyzed game. Evals are for the player who moved.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MoveAnalysis {
+    pub row: usize,
+    pub col: usize,
+    pub player: i8,
+    // Best score available before the move, and the played move's score
+    pub eval_before: i32,
+    pub eval_after: i32,
+    pub blunder: bool,
+    // Engine's move, set when the played one was a blunder
+    pub best: Option<(usize, usize)>,
+}
+
+// The engine's answer to a move the side to move is thinking of. `score`
+// is the search's for the replying side, `eval` the static eval after the
+// reply for the player who tried the move; a move that wins has no reply.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Exploration {
+    pub reply: Option<(usize, usize)>,
+    pub score: i32,
+    pub eval: i32,
+}
+
+// A finished self_play game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub moves: Vec<(usize, usize)>,
+    // None for a draw
+    pub winner: Option<i8>,
+}
+
+// Points for each line shape, see Game::shape. The evaluation and move
+// ordering both score lines from the Game's table, so they can't disagree.
+// Deserializable, to load a tuned table at runtime with Game::set_patterns;
+// shapes a saved table doesn't mention keep their defaults.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PatternTable {
+    pub five: i32,
+    pub open_four: i32,
+    // One end open, or a gap that makes five whatever the ends
+    pub four: i32,
+    // Can't become five any more, worth no more than the stones in it
+    pub dead_four: i32,
+    pub open_three: i32,
+    pub broken_three: i32,
+    pub closed_three: i32,
+    pub open_two: i32,
+    // _X_X_, one stone short of a broken three
+    pub split_two: i32,
+    pub closed_two: i32,
+    // Anything weaker, or with no room to grow
+    pub other: i32,
+}
+
+impl Default for PatternTable {
+    fn default() -> Self {
+        PatternTable {
+            five: WIN,
+            open_four: 10000,
+            four: 5000,
+            dead_four: 10,
+            open_three: 5000,
+            broken_three: 4000,
+            closed_three: 500,
+            open_two: 500,
+            split_two: 300,
+            closed_two: 50,
+            other: 10,
+        }
+    }
+}
+
+impl PatternTable {
+    pub fn score(&self, kind: ThreatKind) -> i32 {
+        match kind {
+            ThreatKind::Five => self.five,
+            ThreatKind::OpenFour => self.open_four,
+            ThreatKind::ClosedFour => self.four,
+            ThreatKind::DeadFour => self.dead_four,
+            ThreatKind::OpenThree => self.open_three,
+            ThreatKind::BrokenThree => self.broken_three,
+            ThreatKind::ClosedThree => self.closed_three,
+            ThreatKind::OpenTwo => self.open_two,
+            ThreatKind::SplitTwo => self.split_two,
+            ThreatKind::ClosedTwo => self.closed_two,
+            ThreatKind::Other => self.other,
+        }
+    }
+}
+
+// Evaluation weights; the defaults lean slightly toward defense
+#[derive(Clone, Copy, Debug)]
+pub struct EvalConfig {
+    // Multiplier on the opponent's score in evaluate, and on the opponent
+    // lines a move blocks in move ordering
+    pub opponent_weight: f32,
+    // Fraction of Black's score dropped in evaluate, to offset the first
+    // move's advantage in AI-vs-AI play, 0 = off
+    pub first_player_penalty: f32,
+    // Points per ring a stone sits in from the edge, divided by one plus
+    // the threats on the board, 0 = off
+    pub center_weight: i32,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        EvalConfig {
+            opponent_weight: 1.1,
+            first_player_penalty: 0.0,
+            center_weight: 3,
+        }
+    }
+}
+
+// What a store does to an existing entry for the same position
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TTReplacement {
+    // The newest entry wins
+    Always,
+    // Keep a deeper entry, unless only the new one has an exact score
+    DepthPreferred,
+}
+
+#[derive(Clone, Copy)]
+pub struct AIConfig {
+    // Max transposition table entries before eviction
+    pub tt_capacity: usize,
+    pub tt_replacement: TTReplacement,
+    // Candidate moves must have a stone within this many cells
+    pub neighbor_radius: usize,
+    // Moves kept after ordering: max_candidates at the root, candidate_decay
+    // fewer per ply below it, never under min_candidates
+    pub max_candidates: usize,
+    pub candidate_decay: usize,
+    pub min_candidates: usize,
+    // Prune quiet nodes where passing still fails high
+    pub null_move: bool,
+    // Search late quiet moves shallower first
+    pub late_move_reductions: bool,
+    // Below the root, search the best few moves first and only go on to
+    // the rest, twice as many each time, while none has raised alpha
+    pub progressive_widening: bool,
+    // Plies a line may gain from moves that make a four or an open three,
+    // one per such move, so forcing sequences are read past the nominal
+    // depth. 0 = off.
+    pub max_extensions: usize,
+    // Order cells touching the opponent's longest line earlier
+    pub threat_proximity: bool,
+    // Never return a move that leaves the opponent a five to play
+    pub must_block: bool,
+    // When the search sees a forced loss, play the candidate the opponent
+    // needs the most moves to win against
+    pub longest_defense: bool,
+    // Look for a win by continuous fours before searching
+    pub vcf: bool,
+    // Then for one by continuous fours and threes
+    pub vct: bool,
+    // Chance, in percent, of playing a random candidate instead of the best move
+    pub blunder_percent: u32,
+    // Root moves within this many points of the best are picked at random, 0 = off
+    pub temperature: i32,
+    // Stones on the board below which book openings are played, 0 = off
+    pub opening_moves: usize,
+    // Answer an opponent mirroring every move through the center
+    pub anti_mirror: bool,
+    // Plies of the search tree kept for AI::trace, 0 = off. Every traced
+    // node is kept, so only for shallow searches.
+    pub trace_depth: usize,
+    // Fixed seed for blunders, temperature and openings, random if unset
+    pub seed: Option<u64>,
+    pub eval: EvalConfig,
+    // Resign when the deepest completed iteration scores below this, None = never
+    pub resign_threshold: Option<i32>,
+}
+
+impl Default for AIConfig {
+    fn default() -> Self {
+        AIConfig {
+            tt_capacity: 1 << 20,
+            tt_replacement: TTReplacement::DepthPreferred,
+            neighbor_radius: 2,
+            max_candidates: 20,
+            candidate_decay: 3,
+            min_candidates: 8,
+            null_move: false,
+            late_move_reductions: true,
+            progressive_widening: false,
+            max_extensions: 0,
+            threat_proximity: true,
+            must_block: true,
+            longest_defense: false,
+            vcf: true,
+            vct: true,
+            blunder_percent: 0,
+            temperature: 0,
+            opening_moves: 0,
+            anti_mirror: true,
+            trace_depth: 0,
+            seed: None,
+            eval: EvalConfig::default(),
+            resign_threshold: Some(-WIN_THRESHOLD),
+        }
+    }
+}
+
+pub struct AI {
+    tt: HashMap<u64, TTEntry>,
+    // Empty until a cutoff happens at that ply
+    killer_moves: Vec<[Option<(usize, usize)>; 2]>,
+    // Cutoff counts per side to move, history[player - 1][row][col], halved
+    // at the start of every search
+    history: [Vec<Vec<i32>>; 2],
+    config: AIConfig,
+    // Inside a null-move or verification search, no nested passes
+    in_null: bool,
+    // Extensions taken on the line being searched, see max_extensions
+    extensions: usize,
+    // Xorshift state for blunders, temperature and openings
+    rng: u64,
+    // Set from another thread to abandon the search in progress
+    stop: Option<Arc<AtomicBool>>,
+    // Deadline of the timed search in progress, for negamax's checks
+    deadline: Option<Instant>,
+    // The deadline passed or the stop flag was seen mid-tree
+    aborted: bool,
+    // Background search from start_ponder, which holds the TT meanwhile
+    ponder: Option<(JoinHandle<AI>, Arc<AtomicBool>)>,
+    stats: SearchStats,
+    // Exact results for one small board, see build_tablebase
+    tablebase: Option<Tablebase>,
+    // Children of each open node on the path being traced, the root's first
+    trace: Vec<Vec<TraceNode>>,
+    // Root moves of the last completed root search
+    last_trace: Vec<TraceNode>,
+    // Best move of each completed iteration of the last search, shallowest
+    // first
+    iteration_moves: Vec<(usize, usize)>,
+    // Deepest iteration of the last search, for checkpoint and find_move_from
+    completed: Option<Completed>,
+}
+
+impl Drop for AI {
+    fn drop(&mut self) {
+        self.stop_ponder();
+    }
+}
+
+impl AI {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self::with_config(rows, cols, AIConfig::default())
+    }
+
+    // Sized for the given game, e.g. one restored with Game::from_state
+    pub fn for_game(game: &Game) -> Self {
+        Self::new(game.rows, game.cols)
+    }
+
+    pub fn with_config(rows: usize, cols: usize, config: AIConfig) -> Self {
+        AI {
+            tt: HashMap::new(),
+            killer_moves: vec![[None; 2]; 32],
+            history: [vec![vec![0; cols]; rows], vec![vec![0; cols]; rows]],
+            config,
+            in_null: false,
+            extensions: 0,
+            rng: config.seed.map_or_else(random_seed, |seed| seed.wrapping_add(0x9E37_79B9_7F4A_7C15).max(1)),
+            stop: None,
+            deadline: None,
+            aborted: false,
+            ponder: None,
+            stats: SearchStats::default(),
+            tablebase: None,
+            trace: Vec::new(),
+            last_trace: Vec::new(),
+            iteration_moves: Vec::new(),
+            completed: None,
+        }
+    }
+
+    // Deepest search expected to fit in `budget_ms` on a size x size board.
+    // Evaluation scans the whole board, so nodes cost more on bigger ones.
+    pub fn suggest_depth(size: usize, budget_ms: u64) -> usize {
+        let node_micros = (size.max(1) * size.max(1)) as f64 * NODE_MICROS_PER_CELL;
+        let budget_nodes = budget_ms as f64 * 1000.0 / node_micros;
+
+        let mut depth = 1;
+        let mut next = ROOT_NODES * EFFECTIVE_BRANCHING;
+        while depth < MAX_DEPTH && next <= budget_nodes {
+            depth += 1;
+            next *= EFFECTIVE_BRANCHING;
+        }
+        depth
+    }
+
+    // Search `game` in the background, e.g. on the opponent's time, until
+    // stop_ponder or the next search. The TT comes back filled in, so
+    // positions after the opponent's reply are partly searched already.
+    pub fn start_ponder(&mut self, game: &Game) {
+        self.stop_ponder();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut worker = AI::with_config(game.rows, game.cols, self.config);
+        worker.tt = std::mem::take(&mut self.tt);
+        worker.stop = Some(Arc::clone(&stop));
+
+        let game = game.clone();
+        let handle = std::thread::spawn(move || {
+            worker.search(&game, MAX_DEPTH, None);
+            worker
+        });
+        self.ponder = Some((handle, stop));
+    }
+
+    pub fn stop_ponder(&mut self) {
+        let Some((handle, stop)) = self.ponder.take() else {
+            return;
+        };
+        stop.store(true, Ordering::Relaxed);
+        // A panicked search loses the table, which only costs speed
+        if let Ok(mut worker) = handle.join() {
+            self.tt = std::mem::take(&mut worker.tt);
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.aborted || self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    // A reused AI keeps its ordering from earlier positions. Killers are for
+    // one search only; history is halved so old cutoffs fade instead of
+    // outweighing the new ones.
+    fn age_ordering(&mut self) {
+        self.killer_moves.iter_mut().for_each(|killers| *killers = [None; 2]);
+        for cell in self.history.iter_mut().flatten().flatten() {
+            *cell /= 2;
+        }
+    }
+
+    // Drop everything learned about earlier positions, e.g. between games.
+    // The next search then plays as a fresh AI would.
+    pub fn clear_tt(&mut self) {
+        self.tt.clear();
+        self.killer_moves.iter_mut().for_each(|killers| *killers = [None; 2]);
+        self.history.iter_mut().flatten().flatten().for_each(|cell| *cell = 0);
+        self.completed = None;
+    }
+
+    // The state the last search left behind, for resume. Depth 0 and no
+    // best move before any search has completed an iteration.
+    pub fn checkpoint(&self) -> SearchCheckpoint {
+        let (rows, cols) = self.board_size();
+        let (position, depth, best) = match self.completed {
+            Some(done) => (done.position, done.depth, Some(done.best)),
+            None => (0, 0, None),
+        };
+        SearchCheckpoint {
+            rows,
+            cols,
+            position,
+            depth,
+            best,
+            iteration_moves: self.iteration_moves.clone(),
+            entries: self.tt.iter().map(|(&hash, &entry)| (hash, entry)).collect(),
+            killer_moves: self.killer_moves.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    // Pick up from a checkpoint, which must be for the same board size.
+    // find_move_from on the checkpoint's position then carries on from the
+    // next depth.
+    pub fn resume(&mut self, checkpoint: SearchCheckpoint) -> Result<(), String> {
+        let (rows, cols) = self.board_size();
+        if (checkpoint.rows, checkpoint.cols) != (rows, cols) {
+            return Err(format!("Checkpoint is for a {}x{} board, not {}x{}", checkpoint.rows, checkpoint.cols, rows, cols));
+        }
+        let on_board = |&(r, c): &(usize, usize)| r < rows && c < cols;
+        let moves_ok = checkpoint.iteration_moves.iter().all(on_board)
+            && checkpoint.best.is_none_or(|(mv, _)| on_board(&mv))
+            && checkpoint.entries.iter().all(|(_, entry)| entry.best_move.is_none_or(|mv| on_board(&mv)))
+            && checkpoint.killer_moves.iter().flatten().flatten().all(on_board);
+        let tables_ok = checkpoint.killer_moves.len() == self.killer_moves.len()
+            && checkpoint.history.iter().all(|side| side.len() == rows && side.iter().all(|row| row.len() == cols));
+        if !moves_ok || !tables_ok {
+            return Err("Checkpoint doesn't fit the board".to_string());
+        }
+
+        self.tt = checkpoint.entries.into_iter().take(self.config.tt_capacity).collect();
+        self.killer_moves = checkpoint.killer_moves;
+        self.history = checkpoint.history;
+        self.iteration_moves = checkpoint.iteration_moves;
+        self.completed = checkpoint.best.map(|best| Completed { position: checkpoint.position, depth: checkpoint.depth, best });
+        Ok(())
+    }
+
+    // Write the transposition table for load_tt in a later run. Zobrist keys
+    // come from a fixed seed, so the hashes stay valid across runs.
+    pub fn save_tt(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let (rows, cols) = self.board_size();
+        let file = TTFile { rows, cols, entries: self.tt.iter().map(|(&hash, &entry)| (hash, entry)).collect() };
+        let json = serde_json::to_string(&file).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    // Replace the transposition table with one from save_tt, which must be
+    // for the same board size
+    pub fn load_tt(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let file: TTFile = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+        let (rows, cols) = self.board_size();
+        if (file.rows, file.cols) != (rows, cols) {
+            return Err(format!("Table is for a {}x{} board, not {}x{}", file.rows, file.cols, rows, cols));
+        }
+        if file.entries.iter().any(|(_, entry)| entry.best_move.is_some_and(|(r, c)| r >= rows || c >= cols)) {
+            return Err("Table has a move off the board".to_string());
+        }
+
+        self.tt = file.entries.into_iter().take(self.config.tt_capacity).collect();
+        Ok(())
+    }
+
+    // Board size the AI was made for, from the history table
+    fn board_size(&self) -> (usize, usize) {
+        let rows = self.history[0].len();
+        (rows, self.history[0].first().map_or(0, |row| row.len()))
+    }
+
+    // Entries are keyed by the canonical form of the position, with the best
+    // move stored in that orientation
+    fn tt_get(&self, game: &Game) -> Option<TTEntry> {
+        let (hash, sym) = game.canonical_hash();
+        self.tt.get(&hash).map(|entry| TTEntry {
+            best_move: entry.best_move.map(|mv| game.zobrist.untransform(sym, mv)),
+            ..*entry
+        })
+    }
+
+    fn tt_put(&mut self, game: &Game, entry: TTEntry) {
+        // Scores from an abandoned search are meaningless
+        if self.stopped() {
+            return;
+        }
+        let (hash, sym) = game.canonical_hash();
+        self.tt_store(hash, TTEntry {
+            best_move: entry.best_move.map(|mv| game.zobrist.transform(sym, mv)),
+            ..entry
+        });
+    }
+
+    fn tt_store(&mut self, hash: u64, entry: TTEntry) {
+        if let (TTReplacement::DepthPreferred, Some(old)) = (self.config.tt_replacement, self.tt.get(&hash)) {
+            let more_exact = entry.flag == TTFlag::Exact && old.flag != TTFlag::Exact;
+            if entry.depth < old.depth && !more_exact {
+                return;
+            }
+        }
+        if self.tt.len() >= self.config.tt_capacity && !self.tt.contains_key(&hash) {
+            // Keep the deeper entries; start over if that doesn't free enough
+            self.tt.retain(|_, e| e.depth > 1);
+            if self.tt.len() >= self.config.tt_capacity / 2 {
+                self.tt.clear();
+            }
+        }
+        self.tt.insert(hash, entry);
+    }
+
+    pub fn find_move(&mut self, game: &Game, depth: usize) -> Option<(usize, usize)> {
+        self.find_move_scored(game, depth).map(|(mv, _)| mv)
+    }
+
+    // Best move plus its score for the side to move
+    pub fn find_move_scored(&mut self, game: &Game, depth: usize) -> Option<((usize, usize), i32)> {
+        self.search(game, depth, None)
+    }
+
+    // What if the side to move played (row, col)? `game` is left as it is.
+    pub fn explore_move(&mut self, game: &Game, (row, col): (usize, usize), depth: usize) -> Result<Exploration, MoveError> {
+        let mut g = game.clone();
+        g.play(row, col)?;
+        if g.winner().is_some() {
+            return Ok(Exploration { reply: None, score: -WIN, eval: WIN });
+        }
+
+        let Some(((r, c), score)) = self.find_move_scored(&g, depth) else {
+            // Board full after the move
+            return Ok(Exploration { reply: None, score: 0, eval: 0 });
+        };
+        g.make_move(r, c);
+        let eval = if g.check_win_at(r, c) { -WIN } else { g.evaluate(&self.config.eval) };
+        Ok(Exploration { reply: Some((r, c)), score, eval })
+    }
+
+    // find_move_scored with the pick checked afterwards: a five of its own is
+    // always played and one of the opponent's always blocked, however weak
+    // or random the rest of the play. Difficulty levels play through this.
+    pub fn find_move_checked(&mut self, game: &Game, depth: usize) -> Option<((usize, usize), i32)> {
+        let result = self.find_move_scored(game, depth);
+        let ((row, col), _) = result?;
+
+        let mut g = game.clone();
+        g.make_move(row, col);
+        if g.check_win_at(row, col) {
+            return result;
+        }
+        let missed_win = game.clone().find_five().is_some();
+        if missed_win || g.find_five().is_some() {
+            return self.immediate_move(game).or(result);
+        }
+        result
+    }
+
+    // find_move_scored, or Resign when the game is lost beyond the threshold.
+    // The score comes from the deepest completed iteration or a forced
+    // line, never a shallow one that deeper search overturned.
+    pub fn find_move_or_resign(&mut self, game: &Game, depth: usize) -> Option<Decision> {
+        let (mv, score) = self.find_move_scored(game, depth)?;
+        Some(if self.resigns(score) { Decision::Resign } else { Decision::Play(mv, score) })
+    }
+
+    pub fn resigns(&self, score: i32) -> bool {
+        self.config.resign_threshold.is_some_and(|threshold| score < threshold)
+    }
+
+    // The `n` best root moves, best first: find_move_scored's move, then the
+    // other candidates rescored at the same depth
+    pub fn find_moves_multi(&mut self, game: &Game, depth: usize, n: usize) -> Vec<((usize, usize), i32)> {
+        let Some(best) = self.find_move_scored(game, depth) else {
+            return Vec::new();
+        };
+
+        let others: Vec<_> = self.get_ordered_moves_phase1(game, 0, None).into_iter().filter(|&mv| mv != best.0).collect();
+        let mut moves: Vec<_> = others.into_iter().map(|mv| (mv, self.root_score(game, mv, depth))).collect();
+        moves.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        moves.insert(0, best);
+        moves.truncate(n);
+        moves
+    }
+
+    // find_move plus counters for the search
+    pub fn find_move_with_stats(&mut self, game: &Game, depth: usize) -> (Option<(usize, usize)>, SearchStats) {
+        let start = Instant::now();
+        let mv = self.find_move(game, depth);
+        self.stats.elapsed_ms = start.elapsed().as_millis() as u64;
+        (mv, self.stats)
+    }
+
+    // Iterative deepening until the time budget runs out.
+    // Only fully completed iterations count.
+    pub fn find_move_timed(&mut self, game: &Game, max_millis: u64) -> Option<((usize, usize), i32)> {
+        let deadline = Instant::now() + Duration::from_millis(max_millis);
+        self.search(game, MAX_DEPTH, Some(deadline))
+    }
+
+    // find_move_scored, calling `on_depth` with the best move so far after
+    // each completed depth. Forced moves are returned without any.
+    pub fn find_move_with_progress(
+        &mut self,
+        game: &Game,
+        depth: usize,
+        mut on_depth: impl FnMut(SearchProgress),
+    ) -> Option<((usize, usize), i32)> {
+        let result = self.search_unchecked(game, depth, 1, None, None, &mut on_depth);
+        let result = self.longest_defense(game, depth, result, None);
+        self.ensure_block(game, result)
+    }
+
+    // find_move_scored from `start_depth` on rather than depth 1. Without
+    // one it carries on after the deepest iteration already completed on
+    // this position, by an earlier search or a resumed checkpoint, whose
+    // result then stands in for the skipped depths.
+    pub fn find_move_from(&mut self, game: &Game, depth: usize, start_depth: Option<usize>) -> Option<((usize, usize), i32)> {
+        let done = self.completed.filter(|done| done.position == game.zobrist.get_hash());
+        let first = start_depth.unwrap_or_else(|| done.map_or(1, |done| done.depth + 1)).max(1);
+        let seed = done.filter(|done| done.depth + 1 == first).map(|done| done.best);
+
+        let result = self.search_unchecked(game, depth, first, seed, None, &mut |_| {});
+        let result = self.longest_defense(game, depth, result, None);
+        self.ensure_block(game, result)
+    }
+
+    // How many of the last search's iterations, counting back from the
+    // deepest, had `mv` as their best move. A move that only came out on top
+    // at the end is less settled than one every depth agreed on. 0 for a
+    // forced move, or one the search didn't pick.
+    pub fn stability(&self, mv: (usize, usize)) -> usize {
+        self.iteration_moves.iter().rev().take_while(|&&best| best == mv).count()
+    }
+
+    // Abandon searches once `stop` is set, e.g. from another thread; the
+    // deepest completed iteration is returned
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = Some(stop);
+    }
+
+    fn search(&mut self, game: &Game, depth: usize, deadline: Option<Instant>) -> Option<((usize, usize), i32)> {
+        let result = self.search_unchecked(game, depth, 1, None, deadline, &mut |_| {});
+        // Timed searches only vouch for the iterations they finished, and
+        // rescoring gets what's left of the time
+        let depth = if deadline.is_some() { self.stats.max_depth } else { depth };
+        let result = self.longest_defense(game, depth, result, deadline);
+        self.ensure_block(game, result)
+    }
+
+    // Iterations run from `first` to `depth`. `seed` is the result of the
+    // iteration before `first`, when carrying on with an earlier search.
+    fn search_unchecked(
+        &mut self,
+        game: &Game,
+        depth: usize,
+        first: usize,
+        seed: Option<((usize, usize), i32)>,
+        deadline: Option<Instant>,
+        on_depth: &mut dyn FnMut(SearchProgress),
+    ) -> Option<((usize, usize), i32)> {
+        self.stop_ponder();
+        // Carrying on keeps the ordering the earlier iterations built up
+        if seed.is_none() {
+            self.age_ordering();
+            self.iteration_moves.clear();
+        }
+        self.stats = SearchStats::default();
+        self.last_trace.clear();
+        let moves = self.get_ordered_moves_phase1(game, 0, None);
+        if let Some(result) = self.forced_move(game) {
+            return Some(result);
+        }
+        if depth == 0 {
+            return self.instant_move(game, &moves);
+        }
+
+        let mut best = seed;
+        self.stats.max_depth = if seed.is_some() { first - 1 } else { 0 };
+        self.deadline = deadline;
+        self.aborted = false;
+        for d in first..=depth {
+            let result = match best {
+                Some((_, score)) => self.aspiration_search(game, d, score, deadline),
+                None => self.search_depth(game, d, -INF, INF, deadline),
+            };
+            match result {
+                Some(result) => {
+                    best = Some(result);
+                    self.stats.max_depth = d;
+                    self.iteration_moves.push(result.0);
+                    self.completed = Some(Completed { position: game.zobrist.get_hash(), depth: d, best: result });
+                    on_depth(SearchProgress { depth: d, best_move: result.0, score: result.1 });
+                }
+                None if deadline.is_some() || self.stopped() => break,
+                None => {}
+            }
+            if deadline.is_some_and(|t| Instant::now() >= t) || self.stopped() {
+                break;
+            }
+        }
+        // Time is up for the iterations only, not for rescoring the result
+        self.deadline = None;
+        self.aborted = false;
+
+        // Out of time before depth 1 finished: fall back to move ordering
+        let best = best.or_else(|| moves.first().map(|&mv| (mv, 0)));
+        self.randomize(game, best, &moves, depth)
+    }
+
+    // Backstop for a chosen move that leaves the opponent a five: win now
+    // or block instead. forced_move should have caught it already.
+    fn ensure_block(&self, game: &Game, result: Option<((usize, usize), i32)>) -> Option<((usize, usize), i32)> {
+        let ((row, col), _) = result?;
+        if !self.config.must_block {
+            return result;
+        }
+
+        let mut g = game.clone();
+        g.make_move(row, col);
+        if g.check_win_at(row, col) || g.find_five().is_none() {
+            return result;
+        }
+        self.immediate_move(game).or(result)
+    }
+
+    // Lost against best play: rescore every candidate, not just the ordered
+    // few, without the pruning that can misjudge how far off the loss is.
+    // Mate scores count plies, so the highest is the slowest loss. Past the
+    // deadline, or if it's reached before every candidate is rescored, the
+    // search's move stands.
+    fn longest_defense(
+        &mut self,
+        game: &Game,
+        depth: usize,
+        result: Option<((usize, usize), i32)>,
+        deadline: Option<Instant>,
+    ) -> Option<((usize, usize), i32)> {
+        let (chosen, score) = result?;
+        if !self.config.longest_defense || depth == 0 || !is_losing_score(score) {
+            return result;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return result;
+        }
 
-        let (mut r, mut c) = (row as i32 - dr, col as i32 - dc);
-        while r >= 0 && r < self.size as i32 && c >= 0 && c < self.size as i32 {
-            if self.board[r as usize][c as usize] == player {
-                count += 1;
-            } else if self.board[r as usize][c as usize] == EMPTY {
-                open += 1;
+        // The chosen move first, so it stays on ties
+        let others = game.candidate_cells(self.config.neighbor_radius).into_iter().filter(|&mv| mv != chosen);
+        let candidates: Vec<_> = std::iter::once(chosen).chain(others).collect();
+
+        let config = self.config;
+        self.config.null_move = false;
+        self.config.late_move_reductions = false;
+        self.deadline = deadline;
+        let mut best: Option<((usize, usize), i32)> = None;
+        for (row, col) in candidates {
+            if self.stopped() {
                 break;
+            }
+            let mut g = game.clone();
+            g.make_move(row, col);
+            let score = if g.check_win_at(row, col) {
+                WIN
             } else {
-                break;
+                -self.negamax(&mut g, depth as i32 - 1, -INF, INF, 1, (row, col))
+            };
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some(((row, col), score));
             }
-            r -= dr;
-            c -= dc;
         }
+        self.config = config;
+        self.deadline = None;
 
-        (count, open)
-    }
-
-    fn has_neighbor(&self, row: usize, col: usize) -> bool {
-        for dr in -2..=2 {
-            for dc in -2..=2 {
-                if dr == 0 && dc == 0 { continue; }
-                let r = row as i32 + dr;
-                let c = col as i32 + dc;
-                if r >= 0 && r < self.size as i32 && c >= 0 && c < self.size as i32 {
-                    if self.board[r as usize][c as usize] != EMPTY {
-                        return true;
-                    }
-                }
-            }
+        let stopped = self.stopped();
+        self.aborted = false;
+        if stopped {
+            return result;
         }
-        false
+        best.or(result)
     }
 
-    fn score_move(&self, row: usize, col: usize) -> i32 {
-        let mut score = 0;
-        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
-
-        let mut temp_board = self.board.clone();
-        temp_board[row][col] = self.current;
+    // Depth 0: the first move in ordering, scored statically
+    fn instant_move(&self, game: &Game, moves: &[(usize, usize)]) -> Option<((usize, usize), i32)> {
+        let &(row, col) = moves.first()?;
+        let mut g = game.clone();
+        g.make_move(row, col);
+        Some(((row, col), -g.evaluate(&self.config.eval)))
+    }
 
-        for &(dr, dc) in &dirs {
-            let (count, open) = Self::eval_line_static(&temp_board, self.size, row, col, dr, dc, self.current);
-            score += match (count, open) {
-                (5.., _) => 50000,
-                (4, _) => 10000,
-                (3, 2) => 5000,
-                (3, 1) => 1000,
-                (2, 2) => 500,
-                _ => 10,
-            };
+    // Blunders and temperature, both off by default. Picked moves are
+    // rescored at the same depth. Forced moves never get here.
+    fn randomize(
+        &mut self,
+        game: &Game,
+        best: Option<((usize, usize), i32)>,
+        moves: &[(usize, usize)],
+        depth: usize,
+    ) -> Option<((usize, usize), i32)> {
+        let (best_move, _) = best?;
+
+        let others: Vec<_> = moves.iter().copied().filter(|&mv| mv != best_move).collect();
+        if !others.is_empty() && self.config.blunder_percent > 0 && self.next_random() % 100 < self.config.blunder_percent as u64 {
+            let mv = others[(self.next_random() % others.len() as u64) as usize];
+            return Some((mv, self.root_score(game, mv, depth)));
         }
 
-        temp_board[row][col] = 3 - self.current;
-        for &(dr, dc) in &dirs {
-            let (count, open) = Self::eval_line_static(&temp_board, self.size, row, col, dr, dc, 3 - self.current);
-            score += match (count, open) {
-                (5.., _) => 50000,
-                (4, _) => 12000,
-                (3, 2) => 6000,
-                (3, 1) => 1100,
-                (2, 2) => 550,
-                _ => 10,
-            };
+        if self.config.temperature > 0 && !others.is_empty() {
+            let scored: Vec<_> = moves.iter().map(|&mv| (mv, self.root_score(game, mv, depth))).collect();
+            let top = scored.iter().map(|&(_, score)| score).max()?;
+            let close: Vec<_> = scored.into_iter().filter(|&(_, score)| score >= top - self.config.temperature).collect();
+            return Some(close[(self.next_random() % close.len() as u64) as usize]);
         }
 
-        score
+        best
     }
 
-    fn eval_line_static(board: &Vec<Vec<i8>>, size: usize, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> (usize, usize) {
-        let mut count = 1;
-        let mut open = 0;
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
 
-        let (mut r, mut c) = (row as i32 + dr, col as i32 + dc);
-        while r >= 0 && r < size as i32 && c >= 0 && c < size as i32 {
-            if board[r as usize][c as usize] == player {
-                count += 1;
-            } else if board[r as usize][c as usize] == EMPTY {
-                open += 1;
-                break;
-            } else {
-                break;
-            }
-            r += dr;
-            c += dc;
-        }
+    // find_move_scored with the root moves split across `threads` workers,
+    // each with its own game copy and transposition table. Root moves get a
+    // full window and no extension, there's no longest_defense, and killers
+    // and history stay per worker, so a close choice can come out
+    // differently.
+    pub fn find_move_parallel(&mut self, game: &Game, depth: usize, threads: usize) -> Option<((usize, usize), i32)> {
+        let result = self.parallel_unchecked(game, depth, threads);
+        self.ensure_block(game, result)
+    }
 
-        let (mut r, mut c) = (row as i32 - dr, col as i32 - dc);
-        while r >= 0 && r < size as i32 && c >= 0 && c < size as i32 {
-            if board[r as usize][c as usize] == player {
-                count += 1;
-            } else if board[r as usize][c as usize] == EMPTY {
-                open += 1;
-                break;
-            } else {
-                break;
-            }
-            r -= dr;
-            c -= dc;
+    fn parallel_unchecked(&mut self, game: &Game, depth: usize, threads: usize) -> Option<((usize, usize), i32)> {
+        self.stop_ponder();
+        let moves = self.get_ordered_moves_phase1(game, 0, None);
+        if let Some(result) = self.forced_move(game) {
+            return Some(result);
+        }
+        if depth == 0 {
+            return self.instant_move(game, &moves);
         }
 
-        (count, open)
-    }
-}
+        let threads = threads.clamp(1, moves.len().max(1));
+        let mut workers: Vec<AI> = (0..threads).map(|_| AI::with_config(game.rows, game.cols, self.config)).collect();
+        let mut best = None;
 
-#[derive(Clone, Copy)]
-struct TTEntry {
-    depth: i32,
-    score: i32,
-    flag: TTFlag,
-    best_move: Option<(usize, usize)>,
-}
+        for d in 1..=depth {
+            // Worker i takes moves i, i + threads, ...
+            let mut scores = vec![-INF; moves.len()];
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = workers
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, worker)| {
+                        let mine: Vec<(usize, (usize, usize))> =
+                            moves.iter().copied().enumerate().skip(i).step_by(threads).collect();
+                        scope.spawn(move || {
+                            mine.into_iter()
+                                .map(|(idx, mv)| (idx, worker.root_score(game, mv, d)))
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    for (idx, score) in handle.join().expect("search thread panicked") {
+                        scores[idx] = score;
+                    }
+                }
+            });
 
-#[derive(Clone, Copy, PartialEq)]
-enum TTFlag {
-    Exact,
-    LowerBound,
-    UpperBound,
-}
+            // Full-window scores; the first of equal moves wins, as in the serial search
+            let mut depth_best: Option<((usize, usize), i32)> = None;
+            for (&mv, &score) in moves.iter().zip(&scores) {
+                if depth_best.is_none_or(|(_, s)| score > s) {
+                    depth_best = Some((mv, score));
+                }
+            }
+            best = depth_best.or(best);
+        }
 
-pub struct AI {
-    tt: HashMap<u64, TTEntry>,
-    killer_moves: Vec<[(usize, usize); 2]>,
-    history: Vec<Vec<i32>>,
-    size: usize,
-}
+        self.randomize(game, best, &moves, depth)
+    }
 
-impl AI {
-    pub fn new(size: usize) -> Self {
-        AI {
-            tt: HashMap::new(),
-            killer_moves: vec![[(size/2, size/2); 2]; 32],
-            history: vec![vec![0; size]; size],
-            size,
+    // Exact score of one root move, for the side to move at the root
+    fn root_score(&mut self, game: &Game, (row, col): (usize, usize), depth: usize) -> i32 {
+        let mut g = game.clone();
+        g.make_move(row, col);
+        if g.check_win_at(row, col) {
+            return WIN;
         }
+        -self.negamax(&mut g, depth as i32 - 1, -INF, INF, 1, (row, col))
     }
 
-    pub fn find_move(&mut self, game: &Game, depth: usize) -> Option<(usize, usize)> {
-        let moves = self.get_ordered_moves_phase1(game, 0, None);
+    // Moves that need no search: a tablebase or book move, an immediate win
+    // or block, or a VCF
+    fn forced_move(&mut self, game: &Game) -> Option<((usize, usize), i32)> {
+        if let Some(result) = self.tablebase_move(game) {
+            return Some(result);
+        }
+        if let Some(mv) = self.book_move(game) {
+            return Some((mv, self.root_score(game, mv, 2)));
+        }
+        if let Some(result) = self.immediate_move(game) {
+            return Some(result);
+        }
 
-        for &(row, col) in &moves {
-            let mut g = game.clone();
-            g.make_move(row, col);
-            if g.check_win(row, col) {
-                return Some((row, col));
+        // Threat lines assume any empty cell can be played
+        if self.config.vcf && !game.gravity {
+            if let Some(line) = self.vcf_search(game, VCF_DEPTH) {
+                return Some((line[0], WIN - (line.len() as i32 - 1)));
+            }
+            if self.config.vct {
+                if let Some(line) = self.vct_search(game, VCT_DEPTH) {
+                    return Some((line[0], WIN - (line.len() as i32 - 1)));
+                }
             }
         }
+        let mv = self.mirror_break(game)?;
+        Some((mv, self.root_score(game, mv, 2)))
+    }
 
-        for &(row, col) in &moves {
-            let mut g = game.clone();
-            g.board[row][col] = 3 - g.current;
-            if g.check_win(row, col) {
-                return Some((row, col));
-            }
-            g.board[row][col] = EMPTY;
+    // Win now, or block the opponent's win. The opponent's threats are
+    // found with real moves after a pass, so the hash stays consistent.
+    fn immediate_move(&self, game: &Game) -> Option<((usize, usize), i32)> {
+        let mut g = game.clone();
+        if let Some(mv) = g.find_five() {
+            return Some((mv, WIN));
         }
 
-        let mut best_move = None;
-        for d in 1..=depth {
-            if let Some(mv) = self.search_depth(game, d) {
-                best_move = Some(mv);
+        // Forced block, lost if it can't hold, otherwise scored statically
+        match game.defense() {
+            Defense::Free => None,
+            Defense::DoubleFour(block) => Some((block, -(WIN - 1))),
+            Defense::FourThree(block) => Some((block, -(WIN - 3))),
+            Defense::Four((row, col)) => {
+                g.make_move(row, col);
+                Some(((row, col), -g.evaluate(&self.config.eval)))
             }
         }
+    }
 
-        best_move
+    // Search a window around the previous iteration's score, widening
+    // whichever side fails until the score lands inside it
+    fn aspiration_search(&mut self, game: &Game, depth: usize, guess: i32, deadline: Option<Instant>) -> Option<((usize, usize), i32)> {
+        let mut below = ASPIRATION_WINDOW;
+        let mut above = ASPIRATION_WINDOW;
+
+        loop {
+            let alpha = (guess - below).max(-INF);
+            let beta = (guess + above).min(INF);
+            let (mv, score) = self.search_depth(game, depth, alpha, beta, deadline)?;
+
+            if score <= alpha && alpha > -INF {
+                below *= 4;
+            } else if score >= beta && beta < INF {
+                above *= 4;
+            } else {
+                return Some((mv, score));
+            }
+        }
     }
 
-    fn search_depth(&mut self, game: &Game, depth: usize) -> Option<(usize, usize)> {
+    // Root search within (alpha, beta). A score <= alpha or >= beta is only a bound.
+    // Returns None if there are no moves or the deadline passed mid-iteration.
+    fn search_depth(&mut self, game: &Game, depth: usize, mut alpha: i32, beta: i32, deadline: Option<Instant>) -> Option<((usize, usize), i32)> {
         let mut best_move = None;
-        let mut alpha = -INF;
-        let beta = INF;
+        self.trace = if self.config.trace_depth > 0 { vec![Vec::new()] } else { Vec::new() };
 
-        let moves = self.get_ordered_moves_phase1(game, 0, None);
+        // The previous iteration's best move goes first
+        let tt_move = self.tt_get(game).and_then(|entry| entry.best_move);
+        let moves = self.get_ordered_moves_phase1(game, 0, tt_move);
 
         for &(row, col) in &moves {
+            if deadline.is_some_and(|d| Instant::now() >= d) || self.stopped() {
+                return None;
+            }
+
             let mut g = game.clone();
             let player = g.current;
             g.make_move(row, col);
 
-            let score = -self.negamax(&mut g, depth as i32 - 1, -beta, -alpha, 1);
+            let traced = self.trace_enter(0);
+            let score = if g.check_win_at(row, col) {
+                WIN
+            } else {
+                let extend = self.extend(&mut g, depth as i32, row, col, player);
+                let score = -self.negamax(&mut g, depth as i32 - 1 + extend, -beta, -alpha, 1, (row, col));
+                self.extensions -= extend as usize;
+                score
+            };
+            if self.stopped() {
+                return None;
+            }
 
             g.undo_move(row, col, player);
 
@@ -366,53 +2733,136 @@ impl AI {
                 alpha = score;
                 best_move = Some((row, col));
             }
+            self.trace_leave(traced, (row, col), score, alpha >= beta);
+            if alpha >= beta {
+                break;
+            }
+        }
+        self.last_trace = self.trace.pop().unwrap_or_default();
+
+        // Store the root so the principal variation can start from it
+        if best_move.is_some() {
+            let flag = if alpha >= beta { TTFlag::LowerBound } else { TTFlag::Exact };
+            self.tt_put(game, TTEntry {
+                depth: depth as i32,
+                score: alpha,
+                flag,
+                best_move,
+            });
         }
 
-        best_move
+        // Failed low: every move scored at most alpha
+        best_move.or(moves.first().copied()).map(|mv| (mv, alpha))
     }
 
-    fn negamax(&mut self, game: &mut Game, depth: i32, mut alpha: i32, beta: i32, ply: usize) -> i32 {
-        let hash = game.zobrist.get_hash();
+    // Expected line of play, following TT best moves from the root
+    pub fn principal_variation(&self, game: &Game, depth: usize) -> Vec<(usize, usize)> {
+        let mut pv = Vec::new();
+        let mut g = game.clone();
+
+        while pv.len() < depth {
+            let Some((row, col)) = self.tt_get(&g).and_then(|e| e.best_move) else {
+                break;
+            };
+            if !g.make_move(row, col) {
+                break;
+            }
+            pv.push((row, col));
+            if g.check_win_at(row, col) {
+                break;
+            }
+        }
+
+        pv
+    }
+
+    // `last` is the move that led here, used by the quiescence search
+    fn negamax(&mut self, game: &mut Game, depth: i32, mut alpha: i32, mut beta: i32, ply: usize, last: (usize, usize)) -> i32 {
+        if self.aborted {
+            return ABORTED;
+        }
+        self.stats.nodes += 1;
+        if self.stats.nodes.is_multiple_of(STOP_CHECK_NODES) {
+            self.aborted = self.stopped() || self.deadline.is_some_and(|d| Instant::now() >= d);
+        }
         let mut tt_move: Option<(usize, usize)> = None;
+        // Taken before the TT narrows the window, so stored bounds stay sound
+        let alpha_orig = alpha;
 
-        if let Some(entry) = self.tt.get(&hash) {
+        if let Some(entry) = self.tt_get(game) {
+            self.stats.tt_hits += 1;
             tt_move = entry.best_move;
 
             if entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
                 match entry.flag {
-                    TTFlag::Exact => return entry.score,
-                    TTFlag::LowerBound => alpha = alpha.max(entry.score),
-                    TTFlag::UpperBound => {}
+                    TTFlag::Exact => return score,
+                    TTFlag::LowerBound => alpha = alpha.max(score),
+                    TTFlag::UpperBound => beta = beta.min(score),
                 }
                 if alpha >= beta {
-                    return entry.score;
+                    return score;
                 }
             }
         }
 
         if depth <= 0 {
-            return game.evaluate();
+            return self.quiescence(game, last, alpha, beta, ply, 0);
+        }
+
+        if self.config.null_move && !self.in_null && ply > 0 && depth >= NULL_MOVE_MIN_DEPTH && game.is_quiet() {
+            if let Some(score) = self.null_move_cutoff(game, depth, beta, ply, last) {
+                return score;
+            }
         }
 
         let moves = self.get_ordered_moves_phase1(game, ply, tt_move);
         if moves.is_empty() {
+            // Nowhere left to play: draw
             return 0;
         }
 
         let mut best_score = -INF;
         let mut best_move: Option<(usize, usize)> = None;
-        let alpha_orig = alpha;
-
-        for &(row, col) in &moves {
+        let mut width = if self.config.progressive_widening && ply > 0 { WIDENING_BASE + 2 * depth as usize } else { moves.len() };
+        // Moves left unsearched, since an earlier one already raised alpha
+        let mut narrowed = false;
+
+        for (i, &(row, col)) in moves.iter().enumerate() {
+            if i == width {
+                if best_score > alpha_orig {
+                    narrowed = true;
+                    break;
+                }
+                width *= 2;
+            }
             let player = game.current;
+            let late = self.config.late_move_reductions && i >= LMR_FULL_MOVES && depth >= LMR_MIN_DEPTH;
+            let blocks_four = late && game.wins_at(row, col, opponent(player));
             game.make_move(row, col);
+            let traced = self.trace_enter(ply);
 
-            if game.check_win(row, col) {
+            if game.check_win_at(row, col) {
                 game.undo_move(row, col, player);
-                return WIN - (depth as i32);
+                self.trace_leave(traced, (row, col), WIN - ply as i32, true);
+                return WIN - ply as i32;
             }
 
-            let score = -self.negamax(game, depth - 1, -beta, -alpha, ply + 1);
+            // Fours, blocks and extended moves are never reduced
+            let extend = self.extend(game, depth, row, col, player);
+            let reduce = late && extend == 0 && !blocks_four && game.winning_cells_through(row, col, player).is_empty();
+            let mut score = -INF;
+            if reduce {
+                score = -self.negamax(game, depth - 2, -alpha - 1, -alpha, ply + 1, (row, col));
+            }
+            if !reduce || score > alpha {
+                // Only the full search's replies go in the trace
+                if let Some(children) = self.trace.last_mut().filter(|_| traced && reduce) {
+                    children.clear();
+                }
+                score = -self.negamax(game, depth - 1 + extend, -beta, -alpha, ply + 1, (row, col));
+            }
+            self.extensions -= extend as usize;
             game.undo_move(row, col, player);
 
             if score > best_score {
@@ -421,27 +2871,29 @@ impl AI {
             }
 
             alpha = alpha.max(score);
+            self.trace_leave(traced, (row, col), score, alpha >= beta);
 
             if alpha >= beta {
                 if let Some(mv) = best_move {
                     self.update_killers(ply, mv);
-                    self.history[mv.0][mv.1] += (depth * depth) as i32;
+                    self.history[(game.current - 1) as usize][mv.0][mv.1] += depth * depth;
                 }
                 break;
             }
         }
 
+        // Skipped moves could only have scored higher
         let flag = if best_score <= alpha_orig {
             TTFlag::UpperBound
-        } else if best_score >= beta {
+        } else if best_score >= beta || narrowed {
             TTFlag::LowerBound
         } else {
             TTFlag::Exact
         };
 
-        self.tt.insert(hash, TTEntry {
+        self.tt_put(game, TTEntry {
             depth,
-            score: best_score,
+            score: score_to_tt(best_score, ply),
             flag,
             best_move,
         });
@@ -449,20 +2901,400 @@ impl AI {
         best_score
     }
 
+    // Start collecting the replies to a move at `ply`, if it's traced. Null
+    // move searches aren't real lines, so they stay out.
+    fn trace_enter(&mut self, ply: usize) -> bool {
+        let traced = ply < self.config.trace_depth && !self.in_null && !self.trace.is_empty();
+        if traced {
+            self.trace.push(Vec::new());
+        }
+        traced
+    }
+
+    // Close the node trace_enter opened and add it under its parent
+    fn trace_leave(&mut self, traced: bool, mv: (usize, usize), score: i32, cutoff: bool) {
+        if !traced {
+            return;
+        }
+        let children = self.trace.pop().unwrap_or_default();
+        if let Some(siblings) = self.trace.last_mut() {
+            siblings.push(TraceNode { mv, score, cutoff, children });
+        }
+    }
+
+    // The last completed root search with config.trace_depth plies of
+    // replies, in the order the moves were searched. Empty if tracing is
+    // off or the move was forced.
+    pub fn trace(&self) -> &[TraceNode] {
+        &self.last_trace
+    }
+
+    // 1 if `player`'s move at (row, col), searched at `depth`, makes a four or
+    // an open three and the line has extensions left, taking one; the caller
+    // gives it back. Not on the last ply: quiescence follows fours from there,
+    // and a full-width reply would hide the five they threaten.
+    fn extend(&mut self, game: &mut Game, depth: i32, row: usize, col: usize, player: i8) -> i32 {
+        if depth <= 1 || self.extensions >= self.config.max_extensions || !game.is_forcing(row, col, player) {
+            return 0;
+        }
+        self.extensions += 1;
+        1
+    }
+
+    // Pass and search shallower with a null window. If that still fails high,
+    // confirm with a reduced search of real moves before cutting off.
+    fn null_move_cutoff(&mut self, game: &mut Game, depth: i32, beta: i32, ply: usize, last: (usize, usize)) -> Option<i32> {
+        self.in_null = true;
+        game.pass();
+        let score = -self.negamax(game, depth - 1 - NULL_MOVE_REDUCTION, -beta, -beta + 1, ply + 1, last);
+        game.pass();
+
+        let cutoff = score >= beta && self.negamax(game, depth - NULL_MOVE_REDUCTION, beta - 1, beta, ply, last) >= beta;
+        self.in_null = false;
+        cutoff.then_some(beta)
+    }
+
+    // Past the horizon, keep following fours until the position is quiet:
+    // win with a five of our own, block the opponent's, or make one and see
+    // it answered. Only the lines through the last move can have created a
+    // new opponent four. `ply` keeps counting from the root, for mate scores.
+    fn quiescence(&self, game: &mut Game, last: (usize, usize), mut alpha: i32, beta: i32, ply: usize, qdepth: usize) -> i32 {
+        let me = game.current;
+        if game.fours[(me - 1) as usize] > 0 && !game.winning_cells(me).is_empty() {
+            return WIN - ply as i32;
+        }
+        if qdepth >= QUIESCENCE_DEPTH {
+            return game.evaluate(&self.config.eval);
+        }
+
+        let threats = game.winning_cells_through(last.0, last.1, opponent(me));
+        match threats.len() {
+            0 => {}
+            1 => {
+                let (row, col) = threats[0];
+                game.make_move(row, col);
+                let score = if game.check_win_at(row, col) {
+                    WIN - ply as i32
+                } else {
+                    -self.quiescence(game, (row, col), -beta, -alpha, ply + 1, qdepth + 1)
+                };
+                game.undo_move(row, col, me);
+                return score;
+            }
+            // Open or double four, only one end can be blocked
+            _ => return -(WIN - (ply as i32 + 1)),
+        }
+
+        // Quiet for now: stand on the static score unless a four does better.
+        // Only a three can become four, open or broken threes being the
+        // ones that win if left alone.
+        let mut best = game.evaluate(&self.config.eval);
+        let i = (me - 1) as usize;
+        if best >= beta || game.threats[i] == game.fours[i] {
+            return best;
+        }
+        alpha = alpha.max(best);
+        for (row, col) in game.candidate_cells(NEIGHBOR_RADIUS) {
+            if game.floats(row, col) {
+                continue;
+            }
+            if !game.makes_four(row, col, me) {
+                continue;
+            }
+
+            game.make_move(row, col);
+            let score = -self.quiescence(game, (row, col), -beta, -alpha, ply + 1, qdepth + 1);
+            game.undo_move(row, col, me);
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    // Victory by continuous fours: every attacking move makes a four, so the
+    // defender's reply is forced. Returns the whole line, ending in the five.
+    // max_depth counts attacker moves; shallower limits are tried first so
+    // the line found is a shortest one.
+    pub fn vcf_search(&mut self, game: &Game, max_depth: usize) -> Option<Vec<(usize, usize)>> {
+        let mut g = game.clone();
+        let defender = opponent(g.current);
+
+        // The defender already threatens five, fours can't outrun that
+        if !g.winning_cells(defender).is_empty() {
+            return None;
+        }
+
+        let mut line = Vec::new();
+        let mut failed = HashMap::new();
+        (1..=max_depth).find(|&depth| Self::vcf(&mut g, depth, &mut line, &mut failed)).map(|_| line)
+    }
+
+    // `failed` maps position hashes to the deepest depth already refuted
+    fn vcf(game: &mut Game, depth: usize, line: &mut Vec<(usize, usize)>, failed: &mut HashMap<u64, usize>) -> bool {
+        let hash = game.zobrist.get_hash();
+        if depth == 0 || failed.get(&hash).is_some_and(|&d| d >= depth) {
+            return false;
+        }
+        let attacker = game.current;
+
+        let mut fours = Vec::new();
+        for row in 0..game.rows {
+            for col in 0..game.cols {
+                if game.board[row][col] != EMPTY || !game.has_neighbor(row, col, NEIGHBOR_RADIUS) || game.is_restricted(row, col) {
+                    continue;
+                }
+                if game.wins_at(row, col, attacker) {
+                    line.push((row, col));
+                    return true;
+                }
+                game.place(row, col, attacker);
+                let threats = game.winning_cells_through(row, col, attacker);
+                game.place(row, col, EMPTY);
+                if !threats.is_empty() {
+                    fours.push(((row, col), threats));
+                }
+            }
+        }
+
+        for ((row, col), threats) in fours {
+            game.make_move(row, col);
+            line.push((row, col));
+
+            // Open four or double four: only one can be blocked
+            if threats.len() >= 2 {
+                line.push(threats[0]);
+                line.push(threats[1]);
+                game.undo_move(row, col, attacker);
+                return true;
+            }
+
+            let (br, bc) = threats[0];
+            game.make_move(br, bc);
+            line.push((br, bc));
+
+            // A block that makes a four hands the initiative back
+            let countered = !game.winning_cells_through(br, bc, opponent(attacker)).is_empty();
+            if !countered && Self::vcf(game, depth - 1, line, failed) {
+                game.undo_move(br, bc, opponent(attacker));
+                game.undo_move(row, col, attacker);
+                return true;
+            }
+
+            line.truncate(line.len() - 2);
+            game.undo_move(br, bc, opponent(attacker));
+            game.undo_move(row, col, attacker);
+        }
+
+        failed.insert(hash, depth);
+        false
+    }
+
+    // Victory by continuous threats: a VCF where open threes count as
+    // threats too. Every reply that stops a three is tried, and the line
+    // returned follows the defender's longest defense.
+    pub fn vct_search(&mut self, game: &Game, max_depth: usize) -> Option<Vec<(usize, usize)>> {
+        let mut g = game.clone();
+        let defender = opponent(g.current);
+
+        if !g.winning_cells(defender).is_empty() {
+            return None;
+        }
+
+        let mut failed = HashMap::new();
+        (1..=max_depth).find_map(|depth| Self::vct(&mut g, depth, &mut failed))
+    }
+
+    fn vct(game: &mut Game, depth: usize, failed: &mut HashMap<u64, usize>) -> Option<Vec<(usize, usize)>> {
+        let hash = game.zobrist.get_hash();
+        if depth == 0 || failed.get(&hash).is_some_and(|&d| d >= depth) {
+            return None;
+        }
+        let attacker = game.current;
+        let defender = opponent(attacker);
+
+        // A four of the defender's answers any three, so only fours are left
+        let fours_only = game.can_make_four(defender);
+
+        // (move, replies, is a four)
+        let mut threats = Vec::new();
+        for row in 0..game.rows {
+            for col in 0..game.cols {
+                if game.board[row][col] != EMPTY || !game.has_neighbor(row, col, NEIGHBOR_RADIUS) || game.is_restricted(row, col) {
+                    continue;
+                }
+                if game.wins_at(row, col, attacker) {
+                    return Some(vec![(row, col)]);
+                }
+                game.place(row, col, attacker);
+                let blocks = game.winning_cells_through(row, col, attacker);
+                if !blocks.is_empty() {
+                    threats.push(((row, col), blocks, true));
+                } else if !fours_only {
+                    let replies = game.three_replies(row, col, attacker);
+                    if !replies.is_empty() {
+                        threats.push(((row, col), replies, false));
+                    }
+                }
+                game.place(row, col, EMPTY);
+            }
+        }
+        // Fours first, they leave a single reply
+        threats.sort_by_key(|&(_, _, four)| !four);
+
+        'threats: for ((row, col), replies, four) in threats {
+            // Open four or double four: only one can be blocked
+            if four && replies.len() >= 2 {
+                return Some(vec![(row, col), replies[0], replies[1]]);
+            }
+
+            game.make_move(row, col);
+            let mut longest: Option<Vec<(usize, usize)>> = None;
+            for &(br, bc) in &replies {
+                game.make_move(br, bc);
+                // A reply that makes a four hands the initiative back
+                let countered = !game.winning_cells_through(br, bc, defender).is_empty();
+                let rest = if countered { None } else { Self::vct(game, depth - 1, failed) };
+                game.undo_move(br, bc, defender);
+
+                let Some(rest) = rest else {
+                    game.undo_move(row, col, attacker);
+                    continue 'threats;
+                };
+                if longest.as_ref().is_none_or(|line| rest.len() + 1 > line.len()) {
+                    longest = Some([vec![(br, bc)], rest].concat());
+                }
+            }
+            game.undo_move(row, col, attacker);
+
+            let mut line = vec![(row, col)];
+            line.extend(longest.unwrap_or_default());
+            return Some(line);
+        }
+
+        failed.insert(hash, depth);
+        None
+    }
+
     fn update_killers(&mut self, ply: usize, mv: (usize, usize)) {
         let ply = ply.min(31);
-        if self.killer_moves[ply][0] != mv {
+        if self.killer_moves[ply][0] != Some(mv) {
             self.killer_moves[ply][1] = self.killer_moves[ply][0];
-            self.killer_moves[ply][0] = mv;
+            self.killer_moves[ply][0] = Some(mv);
+        }
+    }
+
+    // Replays `moves` on an empty size x size board, searching each position.
+    // Stops at the first illegal move or once the game is won.
+    pub fn analyze_game(&mut self, moves: &[(usize, usize)], size: usize) -> Vec<MoveAnalysis> {
+        let mut game = match Game::from_board(vec![vec![EMPTY; size]; size], BLACK) {
+            Ok(game) => game,
+            Err(_) => return Vec::new(),
+        };
+        let mut analysis = Vec::new();
+
+        for &(row, col) in moves {
+            if row >= size || col >= size || game.board[row][col] != EMPTY || game.winner().is_some() {
+                break;
+            }
+
+            let player = game.current;
+            // Forced-move scores aren't comparable with searched ones, so a
+            // different best move is rescored the same way as the played one
+            let (best, score) = self.find_move_scored(&game, ANALYSIS_DEPTH).unwrap_or(((row, col), 0));
+            let (eval_before, eval_after) = if best == (row, col) {
+                (score, score)
+            } else {
+                (self.root_score(&game, best, ANALYSIS_DEPTH), self.root_score(&game, (row, col), ANALYSIS_DEPTH))
+            };
+            let blunder = eval_before - eval_after >= BLUNDER_MARGIN;
+
+            analysis.push(MoveAnalysis {
+                row,
+                col,
+                player,
+                eval_before,
+                eval_after,
+                blunder,
+                best: blunder.then_some(best),
+            });
+
+            if game.play(row, col).is_err() {
+                break;
+            }
+        }
+        analysis
+    }
+
+    // Empty cells near stones where the side to move would make a threat,
+    // with the strongest threat over the four directions
+    pub fn threat_cells(&self, game: &Game) -> Vec<(usize, usize, ThreatKind)> {
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let player = game.current;
+        let mut board = game.board.clone();
+        let mut cells = Vec::new();
+
+        for row in 0..game.rows {
+            for col in 0..game.cols {
+                if board[row][col] != EMPTY
+                    || !game.has_neighbor(row, col, self.config.neighbor_radius)
+                    || game.is_restricted(row, col)
+                {
+                    continue;
+                }
+
+                board[row][col] = player;
+                let kind = dirs
+                    .iter()
+                    .filter_map(|&(dr, dc)| {
+                        // A broken three threatens as an open one does
+                        match game.eval_line_static(&board, row, col, dr, dc, player).kind {
+                            ThreatKind::BrokenThree => Some(ThreatKind::OpenThree),
+                            kind if kind >= ThreatKind::OpenThree => Some(kind),
+                            _ => None,
+                        }
+                    })
+                    .max();
+                board[row][col] = EMPTY;
+
+                if let Some(kind) = kind {
+                    cells.push((row, col, kind));
+                }
+            }
+        }
+        cells
+    }
+
+    // Every candidate cell with its score_move value, best first and not
+    // capped by candidate_limit. Killers and history aren't included.
+    pub fn scored_moves(&self, game: &Game) -> Vec<((usize, usize), i32)> {
+        if game.stone_count() == 0 {
+            return vec![(game.first_move(), 0)];
         }
+
+        let mut moves: Vec<_> = game
+            .candidate_cells(self.config.neighbor_radius)
+            .into_iter()
+            .map(|(row, col)| ((row, col), game.score_move(row, col, &self.config.eval)))
+            .collect();
+        moves.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        moves
+    }
+
+    fn candidate_limit(&self, ply: usize) -> usize {
+        let config = &self.config;
+        let floor = config.min_candidates.min(config.max_candidates);
+        config.max_candidates.saturating_sub(ply * config.candidate_decay).max(floor)
     }
 
     fn get_ordered_moves_phase1(&self, game: &Game, ply: usize, tt_move: Option<(usize, usize)>) -> Vec<(usize, usize)> {
         let mut moves_with_scores = Vec::new();
 
         let mut has_piece = false;
-        for row in 0..game.size {
-            for col in 0..game.size {
+        for row in 0..game.rows {
+            for col in 0..game.cols {
                 if game.board[row][col] != EMPTY {
                     has_piece = true;
                     break;
@@ -472,36 +3304,69 @@ impl AI {
         }
 
         if !has_piece {
-            return vec![(game.size / 2, game.size / 2)];
+            return vec![game.first_move()];
         }
 
         let ply = ply.min(31);
+        let threat = if self.config.threat_proximity { game.longest_run(opponent(game.current)) } else { Vec::new() };
 
-        for row in 0..game.size {
-            for col in 0..game.size {
-                if game.board[row][col] == EMPTY && game.has_neighbor(row, col) {
-                    let mut score = game.score_move(row, col);
-
-                    if Some((row, col)) == tt_move {
-                        score += 10_000_000;
-                    }
+        for (row, col) in game.candidate_cells(self.config.neighbor_radius) {
+            let mut score = game.score_move(row, col, &self.config.eval);
 
-                    if self.killer_moves[ply][0] == (row, col) {
-                        score += 1_000_000;
-                    } else if self.killer_moves[ply][1] == (row, col) {
-                        score += 500_000;
-                    }
+            if Some((row, col)) == tt_move {
+                score += 10_000_000;
+            }
 
-                    score += self.history[row][col];
+            if self.killer_moves[ply][0] == Some((row, col)) {
+                score += 1_000_000;
+            } else if self.killer_moves[ply][1] == Some((row, col)) {
+                score += 500_000;
+            }
 
-                    moves_with_scores.push(((row, col), score));
-                }
+            if threat.iter().any(|&(r, c)| r.abs_diff(row) <= 1 && c.abs_diff(col) <= 1) {
+                score += THREAT_PROXIMITY_BONUS;
             }
+
+            score += self.history[(game.current - 1) as usize][row][col];
+
+            moves_with_scores.push(((row, col), score));
         }
 
-        moves_with_scores.sort_by(|a, b| b.1.cmp(&a.1));
-        moves_with_scores.truncate(15);
+        moves_with_scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+
+        // At the root, moves that are mirror images of a better-ordered one
+        // lead to the same game, so only the first of each is searched
+        if ply == 0 {
+            let symmetries = game.symmetries();
+            let mut kept = Vec::new();
+            moves_with_scores.retain(|&(mv, _)| {
+                let twin = symmetries.iter().any(|&sym| kept.contains(&game.zobrist.transform(sym, mv)));
+                if !twin {
+                    kept.push(mv);
+                }
+                !twin
+            });
+        }
+        moves_with_scores.truncate(self.candidate_limit(ply));
 
         moves_with_scores.into_iter().map(|(m, _)| m).collect()
     }
 }
+
+// Two AIs with the same config play a full game on an empty size x size
+// board. With config.seed set, the same arguments give the same game.
+pub fn self_play(size: usize, depth_black: usize, depth_white: usize, config: AIConfig) -> Result<GameRecord, BoardError> {
+    let mut game = Game::from_board(vec![vec![EMPTY; size]; size], BLACK)?;
+    let mut black = AI::with_config(size, size, config);
+    let mut white = AI::with_config(size, size, config);
+
+    while game.winner().is_none() && !game.is_full() {
+        let (ai, depth) = if game.current == BLACK { (&mut black, depth_black) } else { (&mut white, depth_white) };
+        let Some((row, col)) = ai.find_move(&game, depth) else { break };
+        if game.play(row, col).is_err() {
+            break;
+        }
+    }
+
+    Ok(GameRecord { moves: game.moves().to_vec(), winner: game.winner() })
+}
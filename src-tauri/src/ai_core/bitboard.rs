@@ -0,0 +1,140 @@
+// Bitboard mirror of the board for line scans. Every line in each of the
+// four directions is one u64 lane per player, so runs of stones are found
+// with shifts and trailing_ones instead of walking cells.
+
+// Longest line a lane can hold
+pub(super) const MAX_SIDE: usize = 64;
+
+#[derive(Clone)]
+pub(super) struct Bitboard {
+    cols: usize,
+    // [player - 1][direction][lane]
+    stones: [[Vec<u64>; 4]; 2],
+    // Bits that are on the board, per direction and lane
+    cells: [Vec<u64>; 4],
+}
+
+impl Bitboard {
+    pub(super) fn new(rows: usize, cols: usize) -> Option<Self> {
+        if rows == 0 || cols == 0 || rows > MAX_SIDE || cols > MAX_SIDE {
+            return None;
+        }
+
+        let lanes = [rows, cols, rows + cols - 1, rows + cols - 1];
+        let mut cells = lanes.map(|n| vec![0u64; n]);
+        let mut board = Bitboard {
+            cols,
+            stones: [lanes.map(|n| vec![0; n]), lanes.map(|n| vec![0; n])],
+            cells: lanes.map(|n| vec![0; n]),
+        };
+        for row in 0..rows {
+            for col in 0..cols {
+                for (dir, lanes) in cells.iter_mut().enumerate() {
+                    let (lane, bit) = board.lane(dir, row, col);
+                    lanes[lane] |= 1 << bit;
+                }
+            }
+        }
+        board.cells = cells;
+        Some(board)
+    }
+
+    pub(super) fn set(&mut self, row: usize, col: usize, cell: i8) {
+        for dir in 0..4 {
+            let (lane, bit) = self.lane(dir, row, col);
+            for (i, player) in self.stones.iter_mut().enumerate() {
+                if cell == i as i8 + 1 {
+                    player[dir][lane] |= 1 << bit;
+                } else {
+                    player[dir][lane] &= !(1 << bit);
+                }
+            }
+        }
+    }
+
+    // Same as Game::count_dir: stones of `player` next to (row, col) going (dr, dc)
+    pub(super) fn count_dir(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> usize {
+        let (dir, forward) = direction(dr, dc);
+        let (lane, bit) = self.lane(dir, row, col);
+        run(self.stones[(player - 1) as usize][dir][lane], bit, forward)
+    }
+
+    // Same as scan_line: count allowing one gap, open ends, gapped
+    pub(super) fn scan_line(&self, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> (usize, usize, bool) {
+        let (dir, _) = direction(dr, dc);
+        let (lane, bit) = self.lane(dir, row, col);
+        let mine = self.stones[(player - 1) as usize][dir][lane];
+        let empty = self.cells[dir][lane] & !self.stones[0][dir][lane] & !self.stones[1][dir][lane];
+
+        let mut count = 1;
+        let mut open = 0;
+        let mut gapped = false;
+
+        for forward in [true, false] {
+            let mut pos = bit;
+            loop {
+                let stones = run(mine, pos, forward);
+                count += stones;
+                let Some(next) = step(pos, stones + 1, forward) else { break };
+                if !is_set(empty, next) {
+                    break;
+                }
+                if !gapped && step(next, 1, forward).is_some_and(|after| is_set(mine, after)) {
+                    gapped = true;
+                    pos = next;
+                } else {
+                    open += 1;
+                    break;
+                }
+            }
+        }
+
+        (count, open, gapped)
+    }
+
+    // Directions: rows, columns, diagonals (1, 1), anti-diagonals (1, -1).
+    // The bit grows with the column along rows and with the row otherwise.
+    fn lane(&self, dir: usize, row: usize, col: usize) -> (usize, usize) {
+        match dir {
+            0 => (row, col),
+            1 => (col, row),
+            2 => (row + self.cols - 1 - col, row),
+            _ => (row + col, row),
+        }
+    }
+}
+
+// Direction index for (dr, dc), and whether it runs toward higher bits
+fn direction(dr: i32, dc: i32) -> (usize, bool) {
+    match (dr, dc) {
+        (0, 1) => (0, true),
+        (0, -1) => (0, false),
+        (1, 0) => (1, true),
+        (-1, 0) => (1, false),
+        (1, 1) => (2, true),
+        (-1, -1) => (2, false),
+        (1, -1) => (3, true),
+        _ => (3, false),
+    }
+}
+
+// Set bits next to `bit`, not counting it
+fn run(lane: u64, bit: usize, forward: bool) -> usize {
+    if forward {
+        (lane.checked_shr(bit as u32 + 1).unwrap_or(0)).trailing_ones() as usize
+    } else {
+        (lane.checked_shl((MAX_SIDE - bit) as u32).unwrap_or(0)).leading_ones() as usize
+    }
+}
+
+fn step(bit: usize, by: usize, forward: bool) -> Option<usize> {
+    if forward {
+        Some(bit + by).filter(|&b| b < MAX_SIDE)
+    } else {
+        bit.checked_sub(by)
+    }
+}
+
+fn is_set(lane: u64, bit: usize) -> bool {
+    lane >> bit & 1 == 1
+}
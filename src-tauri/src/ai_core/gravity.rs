@@ -0,0 +1,35 @@
+// Gravity mode, as in Connect-4: a stone dropped in a column lands on the
+// lowest empty cell, so only those cells can be played. Wins and the
+// evaluation are unchanged, they're still runs of win_length.
+
+use super::{Game, MoveError, EMPTY};
+
+impl Game {
+    pub fn set_gravity(&mut self, gravity: bool) {
+        self.gravity = gravity;
+    }
+
+    pub fn gravity(&self) -> bool {
+        self.gravity
+    }
+
+    // Row a stone dropped in `col` lands on, None for a full column
+    pub fn drop_row(&self, col: usize) -> Option<usize> {
+        (0..self.rows).rev().find(|&row| self.board[row][col] == EMPTY)
+    }
+
+    // Play the side to move's stone in `col`, returning the row it lands on
+    pub fn drop_stone(&mut self, col: usize) -> Result<usize, MoveError> {
+        if col >= self.cols {
+            return Err(MoveError::OutOfBounds);
+        }
+        let row = self.drop_row(col).ok_or(MoveError::Occupied)?;
+        self.play(row, col)?;
+        Ok(row)
+    }
+
+    // Empty cell with an empty cell under it, unplayable under gravity
+    pub(super) fn floats(&self, row: usize, col: usize) -> bool {
+        self.gravity && row + 1 < self.rows && self.board[row + 1][col] == EMPTY
+    }
+}
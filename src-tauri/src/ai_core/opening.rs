@@ -0,0 +1,104 @@
+// Opening book for the first few stones. Lines are offsets from the center
+// in one orientation; any of the eight rotations and reflections matches,
+// and the AI picks at random among the moves that continue a match.
+
+use super::{opponent, Game, AI, BLACK, EMPTY, WHITE};
+
+// Stones on the board for mirror_break to step in: at least two copied
+// moves, one could be chance, and still in the opening
+const MIRROR_MIN_STONES: usize = 6;
+const MIRROR_PHASE: usize = 16;
+
+// Black's first stone, White's reply, Black's third. All stay within one
+// cell of the center.
+const OPENINGS: &[&[(i32, i32)]] = &[
+    // Direct: White next to the center stone
+    &[(0, 0), (-1, 0), (-1, 1)],
+    &[(0, 0), (-1, 0), (0, 1)],
+    &[(0, 0), (-1, 0), (1, 1)],
+    // Indirect: White diagonal to it
+    &[(0, 0), (-1, 1), (0, 1)],
+    &[(0, 0), (-1, 1), (1, 1)],
+    &[(0, 0), (-1, 1), (1, 0)],
+];
+
+impl AI {
+    // A book move while fewer than config.opening_moves stones are down and
+    // the stones so far follow one of the OPENINGS
+    pub(super) fn book_move(&mut self, game: &Game) -> Option<(usize, usize)> {
+        let stones = game.stone_count();
+        if stones >= self.config.opening_moves || game.rows < 3 || game.cols < 3 {
+            return None;
+        }
+
+        let mut candidates = Vec::new();
+        for line in OPENINGS.iter().filter(|line| line.len() > stones) {
+            for sym in 0..8 {
+                let follows = line[..stones].iter().enumerate().all(|(i, &offset)| {
+                    let player = if i % 2 == 0 { BLACK } else { WHITE };
+                    cell(game, sym, offset).is_some_and(|(r, c)| game.board[r][c] == player)
+                });
+                let Some((row, col)) = cell(game, sym, line[stones]) else { continue };
+                if follows && game.board[row][col] == EMPTY && !game.is_restricted(row, col) && !candidates.contains(&(row, col)) {
+                    candidates.push((row, col));
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[(self.next_random() % candidates.len() as u64) as usize])
+    }
+
+    // Against an opponent copying our moves through the center: the cell
+    // opposite the one stone they played before they started copying. Its
+    // copy would land on their own stone, so the copying stops, and next to
+    // the center the move also lines up with our center stone. None unless
+    // the side to move holds the center and every other stone faces an
+    // opposite-colored one through the center.
+    pub(super) fn mirror_break(&self, game: &Game) -> Option<(usize, usize)> {
+        let stones = game.stone_count();
+        let odd = !game.rows.is_multiple_of(2) && !game.cols.is_multiple_of(2);
+        if !self.config.anti_mirror || game.gravity || !odd || !(MIRROR_MIN_STONES..MIRROR_PHASE).contains(&stones) {
+            return None;
+        }
+        let (row, col) = game.center();
+        if game.board[row][col] != game.current {
+            return None;
+        }
+
+        let mut unmatched = None;
+        for r in 0..game.rows {
+            for c in 0..game.cols {
+                let stone = game.board[r][c];
+                let (mr, mc) = game.zobrist.transform(3, (r, c));
+                if stone == EMPTY || (r, c) == (row, col) || game.board[mr][mc] == opponent(stone) {
+                    continue;
+                }
+                if stone == game.current || game.board[mr][mc] != EMPTY || unmatched.is_some() {
+                    return None;
+                }
+                unmatched = Some((mr, mc));
+            }
+        }
+        unmatched.filter(|&(r, c)| !game.is_restricted(r, c))
+    }
+}
+
+// Board cell for a center offset after one of the eight symmetries
+fn cell(game: &Game, sym: usize, (dr, dc): (i32, i32)) -> Option<(usize, usize)> {
+    let (dr, dc) = match sym {
+        0 => (dr, dc),
+        1 => (dr, -dc),
+        2 => (-dr, dc),
+        3 => (-dr, -dc),
+        4 => (dc, dr),
+        5 => (dc, -dr),
+        6 => (-dc, dr),
+        _ => (-dc, -dr),
+    };
+    let (row, col) = game.center();
+    let (r, c) = (row as i32 + dr, col as i32 + dc);
+    game.in_bounds(r, c).then_some((r as usize, c as usize))
+}
@@ -0,0 +1,87 @@
+// Training puzzles: random midgame boards where the side to move has
+// exactly one first move that wins by continuous fours.
+
+use super::{Game, AI, BLACK, EMPTY, NEIGHBOR_RADIUS};
+
+// Boards tried before giving up
+const PUZZLE_ATTEMPTS: usize = 500;
+// Stones placed before looking for a win, at least and on top of that at most
+const MIN_STONES: usize = 12;
+const EXTRA_STONES: usize = 16;
+
+impl AI {
+    // A puzzle and its solution: the side to move wins in at most `win_in`
+    // of its own moves, every one of them a four, and no other first move
+    // wins that way. Boards with a five to play at once are skipped. None if
+    // no board worked out within PUZZLE_ATTEMPTS; config.seed makes the
+    // result repeatable.
+    pub fn generate_puzzle(&mut self, size: usize, win_in: usize) -> Option<(Game, (usize, usize))> {
+        if win_in < 2 || size < 5 {
+            return None;
+        }
+
+        for _ in 0..PUZZLE_ATTEMPTS {
+            let Some(mut game) = self.random_midgame(size) else { continue };
+            if game.find_five().is_some() {
+                continue;
+            }
+            // The line alternates attacker and defender moves, ending in
+            // the five; an open four adds one past the search depth
+            let Some(line) = self.vcf_search(&game, win_in) else { continue };
+            if line.len().div_ceil(2) <= win_in && self.unique_vcf(&mut game, line[0], win_in) {
+                return Some((game, line[0]));
+            }
+        }
+        None
+    }
+
+    // Stones dropped next to earlier ones, starting at the center, with
+    // neither side left on a five
+    fn random_midgame(&mut self, size: usize) -> Option<Game> {
+        let mut game = Game::from_board(vec![vec![EMPTY; size]; size], BLACK).ok()?;
+        let stones = MIN_STONES + (self.next_random() % (EXTRA_STONES as u64 + 1)) as usize;
+
+        for _ in 0..stones {
+            let cells = if game.stone_count() == 0 { vec![game.first_move()] } else { game.candidate_cells(1) };
+            if cells.is_empty() {
+                return None;
+            }
+            let (row, col) = cells[(self.next_random() % cells.len() as u64) as usize];
+            game.make_move(row, col);
+            if game.check_win_at(row, col) {
+                return None;
+            }
+        }
+        Some(game)
+    }
+
+    // No four other than `solution` starts a win within `win_in` moves. A
+    // four with one winning cell gets blocked there; one with two has won.
+    fn unique_vcf(&mut self, game: &mut Game, solution: (usize, usize), win_in: usize) -> bool {
+        let attacker = game.current;
+        for (row, col) in game.candidate_cells(NEIGHBOR_RADIUS) {
+            if (row, col) == solution {
+                continue;
+            }
+            game.make_move(row, col);
+            let threats = game.winning_cells_through(row, col, attacker);
+            let wins = match threats.len() {
+                0 => false,
+                1 => {
+                    let (br, bc) = threats[0];
+                    let defender = game.current;
+                    game.make_move(br, bc);
+                    let wins = !game.check_win_at(br, bc) && self.vcf_search(game, win_in - 1).is_some();
+                    game.undo_move(br, bc, defender);
+                    wins
+                }
+                _ => true,
+            };
+            game.undo_move(row, col, attacker);
+            if wins {
+                return false;
+            }
+        }
+        true
+    }
+}
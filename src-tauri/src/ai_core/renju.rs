@@ -0,0 +1,162 @@
+// Renju restrictions for Black: no double-three, double-four or overline.
+// Threes aren't checked recursively: a three whose open-four point is itself
+// forbidden still counts.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Game, BLACK, EMPTY};
+
+const WALL: i8 = -1;
+// Cells -5..=5 around the move, the move itself in the middle
+const SPAN: usize = 11;
+const MID: usize = SPAN / 2;
+
+type Window = [i8; SPAN];
+
+// Why a Black move is forbidden, with the stones of each line involved,
+// the move included
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Forbidden {
+    Overline { run: Vec<(usize, usize)> },
+    DoubleFour { fours: Vec<Vec<(usize, usize)>> },
+    DoubleThree { threes: Vec<Vec<(usize, usize)>> },
+}
+
+impl Game {
+    pub fn set_renju_mode(&mut self, renju: bool) {
+        self.renju_mode = renju;
+    }
+
+    pub fn renju_mode(&self) -> bool {
+        self.renju_mode
+    }
+
+    // Would Black playing here break Renju rules? An exact five is always allowed.
+    pub fn is_forbidden(&self, row: usize, col: usize) -> bool {
+        self.forbidden_reason(row, col).is_some()
+    }
+
+    // The rule Black playing here would break, if any
+    pub fn forbidden_reason(&self, row: usize, col: usize) -> Option<Forbidden> {
+        if self.board[row][col] != EMPTY {
+            return None;
+        }
+
+        let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let windows: Vec<Window> = dirs.iter().map(|&(dr, dc)| self.window(row, col, dr, dc)).collect();
+        // Window indices back to board cells, wrapped as window() read them
+        let cells = |dir: usize, stones: Vec<usize>| -> Vec<(usize, usize)> {
+            let (dr, dc) = dirs[dir];
+            stones
+                .into_iter()
+                .map(|i| i as i32 - MID as i32)
+                .filter_map(|k| self.cell_at(row as i32 + dr * k, col as i32 + dc * k))
+                .collect()
+        };
+
+        if windows.iter().any(|w| run(w, MID) == 5) {
+            return None;
+        }
+        if let Some(dir) = windows.iter().position(|w| run(w, MID) > 5) {
+            let (lo, hi) = run_bounds(&windows[dir], MID);
+            return Some(Forbidden::Overline { run: cells(dir, (lo..=hi).collect()) });
+        }
+
+        let fours: Vec<_> = windows
+            .iter()
+            .enumerate()
+            .flat_map(|(dir, w)| four_lines(w).into_iter().map(move |stones| (dir, stones)))
+            .map(|(dir, stones)| cells(dir, stones))
+            .collect();
+        if fours.len() >= 2 {
+            return Some(Forbidden::DoubleFour { fours });
+        }
+
+        let threes: Vec<_> = windows
+            .iter()
+            .enumerate()
+            .filter_map(|(dir, w)| three_line(w).map(|stones| cells(dir, stones)))
+            .collect();
+        (threes.len() >= 2).then_some(Forbidden::DoubleThree { threes })
+    }
+
+    // Moves the side to move may not play
+    pub(super) fn is_restricted(&self, row: usize, col: usize) -> bool {
+        self.floats(row, col) || (self.renju_mode && self.current == BLACK && self.is_forbidden(row, col))
+    }
+
+    fn window(&self, row: usize, col: usize, dr: i32, dc: i32) -> Window {
+        let mut w = [WALL; SPAN];
+        for (i, cell) in w.iter_mut().enumerate() {
+            let k = i as i32 - MID as i32;
+            if let Some((r, c)) = self.cell_at(row as i32 + dr * k, col as i32 + dc * k) {
+                *cell = self.board[r][c];
+            }
+        }
+        w[MID] = BLACK;
+        w
+    }
+}
+
+// Length of the black run through index i
+fn run(w: &Window, i: usize) -> usize {
+    let (lo, hi) = run_bounds(w, i);
+    hi - lo + 1
+}
+
+// First and last index of the black run through index i
+fn run_bounds(w: &Window, i: usize) -> (usize, usize) {
+    let back = w[..i].iter().rev().take_while(|&&v| v == BLACK).count();
+    let fwd = w[i + 1..].iter().take_while(|&&v| v == BLACK).count();
+    (i - back, i + fwd)
+}
+
+// Empty cells that would turn the stones through the middle into exactly five
+fn five_points(w: &Window) -> Vec<usize> {
+    (1..SPAN - 1)
+        .filter(|&e| w[e] == EMPTY)
+        .filter(|&e| {
+            let mut next = *w;
+            next[e] = BLACK;
+            let (lo, hi) = (e.min(MID), e.max(MID));
+            run(&next, e) == 5 && next[lo..=hi].iter().all(|&v| v == BLACK)
+        })
+        .collect()
+}
+
+// Stones of each four through the middle. An open four's two ends are
+// one four; X_XXX_X is two.
+fn four_lines(w: &Window) -> Vec<Vec<usize>> {
+    let points = five_points(w);
+    if points.len() == 2 && points[1] - points[0] == 5 {
+        return vec![(points[0] + 1..points[1]).collect()];
+    }
+    points
+        .into_iter()
+        .map(|e| {
+            let mut next = *w;
+            next[e] = BLACK;
+            let (lo, hi) = run_bounds(&next, e);
+            (lo..=hi).filter(|&i| i != e).collect()
+        })
+        .collect()
+}
+
+fn is_open_four(w: &Window) -> bool {
+    let points = five_points(w);
+    points.len() == 2 && points[1] - points[0] == 5
+}
+
+// Stones of the open three through the middle: one more stone makes an
+// open four
+fn three_line(w: &Window) -> Option<Vec<usize>> {
+    if !five_points(w).is_empty() {
+        return None;
+    }
+    (1..SPAN - 1).filter(|&e| w[e] == EMPTY).find_map(|e| {
+        let mut next = *w;
+        next[e] = BLACK;
+        let points = five_points(&next);
+        is_open_four(&next).then(|| (points[0] + 1..points[1]).filter(|&i| i != e).collect())
+    })
+}
@@ -0,0 +1,107 @@
+// Swap2 opening: the first player places two black stones and one white,
+// the second player then swaps, stays as White, or adds one stone of each
+// color and hands the color choice back.
+
+use super::{Game, AI, BLACK, EMPTY, WHITE};
+
+// How far from even the opening must be before taking a side outright
+const SWAP2_MARGIN: i32 = 300;
+const PLACE_CANDIDATES: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Swap2Phase {
+    // First player places the three opening stones
+    PlaceThree,
+    // Second player picks Swap, Stay or PlaceTwo
+    ChooseOption,
+    // First player picks a color after PlaceTwo
+    ChooseColor,
+    // Normal play
+    Done,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Swap2Option {
+    // Take Black, the opponent plays White's next move
+    Swap,
+    // Keep White and play `reply`
+    Stay { reply: (usize, usize) },
+    // Add a black and a white stone, the opponent picks a color
+    PlaceTwo { black: (usize, usize), white: (usize, usize) },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Swap2Color {
+    Black,
+    White { reply: (usize, usize) },
+}
+
+pub fn swap2_phase(game: &Game) -> Swap2Phase {
+    match game.stone_count() {
+        0..=2 => Swap2Phase::PlaceThree,
+        3 => Swap2Phase::ChooseOption,
+        5 => Swap2Phase::ChooseColor,
+        _ => Swap2Phase::Done,
+    }
+}
+
+impl AI {
+    // Second player's decision after the opening three, White to move
+    pub fn swap2_choose_option(&mut self, game: &Game) -> Option<Swap2Option> {
+        let (reply, score) = self.swap2_balance(game)?;
+
+        if score < -SWAP2_MARGIN {
+            return Some(Swap2Option::Swap);
+        }
+        if score > SWAP2_MARGIN {
+            return Some(Swap2Option::Stay { reply });
+        }
+
+        // Close to even: add the pair that keeps it closest to even
+        let mut best = None;
+        let mut best_imbalance = i32::MAX;
+        let mut g = game.clone();
+        g.current = BLACK;
+        for black in self.placement_candidates(&g) {
+            g.put(black.0, black.1, BLACK);
+            g.current = WHITE;
+            for white in self.placement_candidates(&g) {
+                g.put(white.0, white.1, WHITE);
+                let imbalance = g.evaluate(&self.config.eval).abs();
+                if imbalance < best_imbalance {
+                    best_imbalance = imbalance;
+                    best = Some(Swap2Option::PlaceTwo { black, white });
+                }
+                g.put(white.0, white.1, EMPTY);
+            }
+            g.put(black.0, black.1, EMPTY);
+            g.current = BLACK;
+        }
+
+        best
+    }
+
+    // First player's color choice after PlaceTwo, White to move
+    pub fn swap2_choose_color(&mut self, game: &Game) -> Option<Swap2Color> {
+        let (reply, score) = self.swap2_balance(game)?;
+        if score >= 0 {
+            Some(Swap2Color::White { reply })
+        } else {
+            Some(Swap2Color::Black)
+        }
+    }
+
+    // Shallow score for the side to move. Odd and even depths favor
+    // whoever moves last, so average one of each.
+    fn swap2_balance(&mut self, game: &Game) -> Option<((usize, usize), i32)> {
+        let (_, even) = self.find_move_scored(game, 2)?;
+        let (reply, odd) = self.find_move_scored(game, 3)?;
+        Some((reply, (even + odd) / 2))
+    }
+
+    fn placement_candidates(&self, game: &Game) -> Vec<(usize, usize)> {
+        let mut moves = self.get_ordered_moves_phase1(game, 0, None);
+        moves.truncate(PLACE_CANDIDATES);
+        moves
+    }
+}
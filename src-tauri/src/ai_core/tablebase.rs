@@ -0,0 +1,136 @@
+// Perfect play on tiny boards. Every position reachable from the empty
+// board is solved once, keyed by its canonical hash so symmetric positions
+// share an entry, and the search plays straight from the table.
+
+use std::collections::HashMap;
+
+use super::{Game, AI, BLACK, EMPTY, WIN};
+
+// 4x4 has a few million reachable positions; every extra cell multiplies
+// that, so 5x5 and up are out of reach of a full solve
+const TABLEBASE_MAX_CELLS: usize = 16;
+// Value of making five now. Each ply further away is one closer to zero.
+const TB_WIN: i8 = 100;
+
+pub(super) struct Tablebase {
+    rows: usize,
+    cols: usize,
+    win_length: usize,
+    // Side to move wins (> 0), draws (0) or loses (< 0)
+    values: HashMap<u64, i8>,
+}
+
+impl Tablebase {
+    fn covers(&self, game: &Game) -> bool {
+        (game.rows, game.cols, game.win_length) == (self.rows, self.cols, self.win_length)
+            && game.allow_overline
+            && !game.renju_mode
+            && !game.gravity
+    }
+
+    fn value(&self, game: &Game) -> Option<i8> {
+        self.values.get(&game.canonical_hash().0).copied()
+    }
+}
+
+impl AI {
+    // Solve size x size with freestyle rules and the given win length. The
+    // table is used for games with exactly those settings from then on.
+    pub fn build_tablebase(&mut self, size: usize, win_length: usize) -> Result<(), String> {
+        if size == 0 || size * size > TABLEBASE_MAX_CELLS {
+            return Err(format!(
+                "Tablebases go up to {} cells (4x4), {}x{} has too many positions to solve",
+                TABLEBASE_MAX_CELLS, size, size
+            ));
+        }
+        let mut game = Game::from_board(vec![vec![EMPTY; size]; size], BLACK).map_err(|e| e.to_string())?;
+        game.set_win_length(win_length);
+
+        let mut values = HashMap::new();
+        solve(&mut game, &mut values);
+        self.tablebase = Some(Tablebase { rows: size, cols: size, win_length, values });
+        Ok(())
+    }
+
+    // Best move from the table, scored like the search scores wins and losses
+    pub(super) fn tablebase_move(&self, game: &Game) -> Option<((usize, usize), i32)> {
+        let tablebase = self.tablebase.as_ref().filter(|tb| tb.covers(game))?;
+        tablebase.value(game)?;
+
+        let mut g = game.clone();
+        let mut best: Option<((usize, usize), i8)> = None;
+        for (row, col) in empty_cells(game) {
+            push(&mut g, row, col);
+            let value = if g.check_win_at(row, col) { Some(TB_WIN) } else { tablebase.value(&g).map(step_back) };
+            pop(&mut g, row, col);
+
+            if let Some(value) = value.filter(|&v| best.is_none_or(|(_, b)| v > b)) {
+                best = Some(((row, col), value));
+            }
+        }
+
+        // TB_WIN - |value| is the ply the five lands on
+        best.map(|(mv, value)| {
+            let score = match value {
+                0 => 0,
+                v if v > 0 => WIN - (TB_WIN - v) as i32,
+                v => -(WIN - (TB_WIN + v) as i32),
+            };
+            (mv, score)
+        })
+    }
+}
+
+fn solve(game: &mut Game, values: &mut HashMap<u64, i8>) -> i8 {
+    let hash = game.canonical_hash().0;
+    if let Some(&value) = values.get(&hash) {
+        return value;
+    }
+
+    // A full board with no five is a draw
+    let mut best = None;
+    for (row, col) in empty_cells(game) {
+        push(game, row, col);
+        let value = if game.check_win_at(row, col) { TB_WIN } else { step_back(solve(game, values)) };
+        pop(game, row, col);
+
+        best = best.max(Some(value));
+        if value == TB_WIN {
+            break;
+        }
+    }
+
+    let value = best.unwrap_or(0);
+    values.insert(hash, value);
+    value
+}
+
+// make_move and undo_move without the evaluation upkeep, which dominates
+// the cost here and isn't needed. The game's eval totals go stale.
+fn push(game: &mut Game, row: usize, col: usize) {
+    let player = game.current;
+    game.place(row, col, player);
+    game.zobrist.toggle(row, col, player);
+    game.history.push((row, col));
+    game.pass();
+}
+
+fn pop(game: &mut Game, row: usize, col: usize) {
+    game.pass();
+    game.history.pop();
+    game.place(row, col, EMPTY);
+    game.zobrist.toggle(row, col, game.current);
+}
+
+// The opponent's value one ply later, seen from the side to move now
+fn step_back(value: i8) -> i8 {
+    let value = -value;
+    value - value.signum()
+}
+
+fn empty_cells(game: &Game) -> Vec<(usize, usize)> {
+    (0..game.rows)
+        .flat_map(|row| (0..game.cols).map(move |col| (row, col)))
+        .filter(|&(row, col)| game.board[row][col] == EMPTY)
+        .collect()
+}
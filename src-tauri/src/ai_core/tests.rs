@@ -0,0 +1,2169 @@
+// Engine tests. Positions are built stone by stone on an empty board, rows
+// and columns counted from the top left.
+
+use super::*;
+
+fn board(size: usize, stones: &[(usize, usize, i8)]) -> Vec<Vec<i8>> {
+    let mut board = vec![vec![EMPTY; size]; size];
+    for &(row, col, player) in stones {
+        board[row][col] = player;
+    }
+    board
+}
+
+fn game(stones: &[(usize, usize, i8)], current: i8) -> Game {
+    Game::from_board(board(15, stones), current).expect("valid test position")
+}
+
+// White's stones out of the way in the corners, for positions about Black
+const WHITE_CORNERS: [(usize, usize, i8); 6] = [(0, 0, 2), (0, 14, 2), (14, 0, 2), (14, 14, 2), (0, 7, 2), (14, 7, 2)];
+
+fn with_corners(stones: &[(usize, usize, i8)], whites: usize) -> Vec<(usize, usize, i8)> {
+    stones.iter().copied().chain(WHITE_CORNERS[..whites].iter().copied()).collect()
+}
+
+#[test]
+fn quiescence_sees_open_three_past_horizon() {
+    // Black has open threes on row 7 and column 10, White has just blocked
+    // the row one at (7, 4). The static score can't tell the column three
+    // becomes an open four.
+    let mut g = game(
+        &with_corners(&[(7, 5, 1), (7, 6, 1), (7, 7, 1), (4, 10, 1), (5, 10, 1), (6, 10, 1), (7, 4, 2)], 5),
+        BLACK,
+    );
+    let ai = AI::new(15, 15);
+    assert!(ai.quiescence(&mut g, (7, 4), -INF, INF, 0, 0) >= WIN_THRESHOLD);
+}
+
+#[test]
+fn quiescence_wins_before_blocking() {
+    // White's last move made a four on row 0, but Black's four on row 7
+    // finishes first
+    let mut g = game(
+        &[(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 6, 1), (7, 2, 2), (0, 3, 2), (0, 4, 2), (0, 5, 2), (0, 6, 2), (11, 11, 1)],
+        BLACK,
+    );
+    let ai = AI::new(15, 15);
+    assert_eq!(ai.quiescence(&mut g, (0, 6), -INF, INF, 0, 0), WIN);
+
+    // Still a win when White's four is open
+    let mut g = game(
+        &[(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 6, 1), (7, 2, 2), (3, 3, 2), (3, 4, 2), (3, 5, 2), (3, 6, 2), (11, 11, 1)],
+        BLACK,
+    );
+    assert_eq!(ai.quiescence(&mut g, (3, 6), -INF, INF, 0, 0), WIN);
+}
+
+fn gravity_game(size: usize, win_length: usize) -> Game {
+    let mut g = Game::from_board(vec![vec![EMPTY; size]; size], BLACK).unwrap();
+    g.set_gravity(true);
+    g.set_win_length(win_length);
+    g
+}
+
+#[test]
+fn gravity_stone_lands_on_lowest_empty_cell() {
+    let mut g = gravity_game(6, 4);
+    assert_eq!(g.drop_stone(2), Ok(5));
+    assert_eq!(g.drop_stone(2), Ok(4));
+    assert_eq!(g.drop_stone(3), Ok(5));
+    assert_eq!(g.cell(4, 2), Cell::White);
+    assert_eq!(g.play(0, 0), Err(MoveError::Floating));
+    assert_eq!(g.drop_stone(6), Err(MoveError::OutOfBounds));
+
+    for _ in 0..4 {
+        g.drop_stone(2).unwrap();
+    }
+    assert_eq!(g.drop_row(2), None);
+    assert_eq!(g.drop_stone(2), Err(MoveError::Occupied));
+}
+
+#[test]
+fn gravity_vertical_four_wins() {
+    let mut g = gravity_game(6, 4);
+    for col in [0, 1, 0, 1, 0, 1] {
+        g.drop_stone(col).unwrap();
+    }
+    assert_eq!(g.winner(), None);
+    assert_eq!(g.drop_stone(0), Ok(2));
+    assert_eq!(g.winner(), Some(BLACK));
+}
+
+#[test]
+fn gravity_keys_only_share_left_right_mirrors() {
+    let mut g = gravity_game(7, 4);
+    for col in [1, 1, 2] {
+        g.drop_stone(col).unwrap();
+    }
+    let mut mirror = gravity_game(7, 4);
+    for col in [5, 5, 4] {
+        mirror.drop_stone(col).unwrap();
+    }
+    // The same stones hanging from the top, which gravity can't reach
+    let mut upside_down = Game::from_board(board(7, &[(0, 1, 1), (1, 1, 2), (0, 2, 1)]), WHITE).unwrap();
+    upside_down.set_gravity(true);
+    upside_down.set_win_length(4);
+
+    assert_eq!(g.canonical_hash().0, mirror.canonical_hash().0);
+    assert_ne!(g.canonical_hash().0, upside_down.canonical_hash().0);
+
+    // A best move stored from one side comes back mirrored on the other
+    let mut ai = AI::new(7, 7);
+    ai.tt_put(&mirror, TTEntry { depth: 1, score: 0, flag: TTFlag::Exact, best_move: Some((6, 3)) });
+    assert_eq!(ai.tt_get(&g).and_then(|entry| entry.best_move), Some((6, 3)));
+    ai.tt_put(&mirror, TTEntry { depth: 1, score: 0, flag: TTFlag::Exact, best_move: Some((5, 4)) });
+    assert_eq!(ai.tt_get(&g).and_then(|entry| entry.best_move), Some((5, 2)));
+    assert!(ai.tt_get(&upside_down).is_none());
+
+    let (row, col) = ai.find_move(&upside_down, 2).unwrap();
+    assert!(!upside_down.floats(row, col));
+}
+
+#[test]
+fn null_move_pruning_keeps_the_tactical_move() {
+    // (7, 7) fours row 7 and makes an open three on column 7, a win once
+    // White has blocked the four
+    let g = game(&with_corners(&[(7, 4, 1), (7, 5, 1), (7, 6, 1), (5, 7, 1), (6, 7, 1), (7, 3, 2)], 4), BLACK);
+    let config = AIConfig { vcf: false, vct: false, null_move: false, ..Default::default() };
+    let plain = AI::with_config(15, 15, config).find_move_scored(&g, 5).unwrap();
+    let pruned = AI::with_config(15, 15, AIConfig { null_move: true, ..config }).find_move_scored(&g, 5).unwrap();
+    assert_eq!(plain.0, (7, 7));
+    assert_eq!(pruned.0, plain.0);
+    assert!(is_winning_score(pruned.1));
+}
+
+#[test]
+fn candidate_cap_narrows_below_root() {
+    let g = game(
+        &[(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2), (8, 6, 1), (6, 8, 2), (9, 9, 1), (5, 5, 2), (9, 7, 1), (10, 10, 2)],
+        BLACK,
+    );
+    let ai = AI::new(15, 15);
+    let root = ai.get_ordered_moves_phase1(&g, 0, None).len();
+    let deep = ai.get_ordered_moves_phase1(&g, 4, None).len();
+    assert_eq!(root, ai.config.max_candidates);
+    assert_eq!(deep, ai.config.min_candidates);
+    assert!(root > deep);
+}
+
+#[test]
+fn vct_finds_win_plain_search_misses() {
+    // No four to make yet, but (5, 7) makes open threes on row 5 and
+    // column 7 at once and White can only stop one
+    let g = game(&[(5, 5, 1), (5, 6, 1), (6, 7, 1), (7, 7, 1), (0, 10, 2), (0, 12, 2), (14, 14, 2), (14, 0, 2)], BLACK);
+    let mut ai = AI::new(15, 15);
+    assert!(ai.vcf_search(&g, VCF_DEPTH).is_none());
+    let line = ai.vct_search(&g, VCT_DEPTH).expect("a win by threats");
+
+    let plain = AIConfig { vct: false, ..Default::default() };
+    let (_, score) = AI::with_config(15, 15, plain).find_move_scored(&g, 1).unwrap();
+    assert!(!is_winning_score(score));
+
+    let (mv, score) = AI::new(15, 15).find_move_scored(&g, 1).unwrap();
+    assert_eq!(mv, line[0]);
+    assert!(is_winning_score(score));
+}
+
+#[test]
+fn parallel_search_matches_serial() {
+    let config = AIConfig { vcf: false, vct: false, ..Default::default() };
+    // A four-three for Black at (7, 7), and a quiet opening
+    let tactical = game(&with_corners(&[(7, 4, 1), (7, 5, 1), (7, 6, 1), (5, 7, 1), (6, 7, 1), (7, 3, 2)], 4), BLACK);
+    let quiet = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2)], BLACK);
+
+    for g in [tactical, quiet] {
+        let serial = AI::with_config(15, 15, config).find_move_scored(&g, 3).unwrap();
+        let parallel = AI::with_config(15, 15, config).find_move_parallel(&g, 3, 4).unwrap();
+        assert_eq!(parallel, serial);
+    }
+}
+
+// Six stones, no threats and nothing for the opening book or mirror
+// breaking to take over
+const MIDGAME: [(usize, usize, i8); 6] = [(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2), (9, 6, 1), (8, 5, 2)];
+
+#[test]
+fn every_difficulty_plays_a_legal_move() {
+    let g = game(&MIDGAME, BLACK);
+    for level in [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert] {
+        let mut ai = AI::with_config(15, 15, level.config());
+        let (row, col) = ai.find_move(&g, level.depth()).unwrap();
+        assert!(g.clone().play(row, col).is_ok(), "{level:?} played ({row}, {col})");
+    }
+}
+
+#[test]
+fn beginner_sometimes_blunders() {
+    let g = game(&MIDGAME, BLACK);
+    let level = Difficulty::Beginner;
+    let pick = |blunder_percent: u32, seed: u64| {
+        let config = AIConfig { blunder_percent, seed: Some(seed), ..level.config() };
+        AI::with_config(15, 15, config).find_move(&g, level.depth()).unwrap()
+    };
+
+    let best = pick(0, 1);
+    assert!((1..=40).all(|seed| pick(0, seed) == best));
+    let blunders = (1..=40).filter(|&seed| pick(level.config().blunder_percent, seed) != best).count();
+    assert!((1..40).contains(&blunders), "{blunders} blunders in 40");
+}
+
+#[test]
+fn side_to_move_changes_the_hash() {
+    let stones = [(7, 7, 1), (7, 8, 2)];
+    let black = game(&stones, BLACK);
+    let white = game(&stones, WHITE);
+    assert_ne!(black.zobrist.get_hash(), white.zobrist.get_hash());
+
+    // Making and undoing a move returns both the stones and the side
+    let mut g = black.clone();
+    g.make_move(8, 8);
+    g.undo_move(8, 8, BLACK);
+    assert_eq!(g.zobrist.get_hash(), black.zobrist.get_hash());
+    g.pass();
+    assert_eq!(g.zobrist.get_hash(), white.zobrist.get_hash());
+}
+
+#[test]
+fn vcf_finds_win_by_fours() {
+    // (6, 7) fours the anti-diagonal, then (7, 7) fours row 7 and column 7
+    // together. Either order wins in two fours.
+    let mut g = game(
+        &with_corners(&[(7, 4, 1), (7, 5, 1), (7, 6, 1), (4, 7, 1), (5, 7, 1), (5, 8, 1), (4, 9, 1), (7, 3, 2), (3, 7, 2), (3, 10, 2)], 4),
+        BLACK,
+    );
+    let mut ai = AI::new(15, 15);
+    assert!(ai.vcf_search(&g, 1).is_none());
+    let line = ai.vcf_search(&g, VCF_DEPTH).expect("a win by fours");
+    assert_eq!(line.len(), 5);
+
+    // Every Black move is a four with one reply, until the double four
+    for pair in line[..3].chunks(2) {
+        let (row, col) = pair[0];
+        assert!(g.play(row, col).is_ok());
+        let threats = g.winning_cells_through(row, col, BLACK);
+        if pair.len() == 2 {
+            assert_eq!(threats, vec![pair[1]]);
+            assert!(g.play(pair[1].0, pair[1].1).is_ok());
+        } else {
+            assert_eq!(threats.len(), 2);
+            assert!(threats.contains(&line[3]) && threats.contains(&line[4]));
+        }
+    }
+}
+
+fn renju_game(stones: &[(usize, usize, i8)]) -> Game {
+    let mut g = game(stones, BLACK);
+    g.set_renju_mode(true);
+    g
+}
+
+#[test]
+fn renju_double_three_is_forbidden() {
+    // (7, 7) makes open threes on row 7 and column 7
+    let mut g = renju_game(&with_corners(&[(7, 5, 1), (7, 6, 1), (5, 7, 1), (6, 7, 1)], 4));
+    assert!(matches!(g.forbidden_reason(7, 7), Some(Forbidden::DoubleThree { threes }) if threes.len() == 2));
+    assert_eq!(g.play(7, 7), Err(MoveError::Forbidden));
+    assert!(!AI::new(15, 15).get_ordered_moves_phase1(&g, 0, None).contains(&(7, 7)));
+
+    // One of the threes blocked at an end is no longer open
+    let g = renju_game(&with_corners(&[(7, 5, 1), (7, 6, 1), (5, 7, 1), (6, 7, 1), (7, 8, 2)], 3));
+    assert!(!g.is_forbidden(7, 7));
+}
+
+#[test]
+fn renju_double_four_is_forbidden() {
+    // (7, 7) makes fours on row 7 and column 7, each blocked at one end
+    let g = renju_game(&with_corners(&[(7, 4, 1), (7, 5, 1), (7, 6, 1), (4, 7, 1), (5, 7, 1), (6, 7, 1), (7, 3, 2), (3, 7, 2)], 4));
+    assert!(matches!(g.forbidden_reason(7, 7), Some(Forbidden::DoubleFour { fours }) if fours.len() == 2));
+
+    // Also two fours on one line, X_XXX_X
+    let g = renju_game(&with_corners(&[(7, 3, 1), (7, 5, 1), (7, 6, 1), (7, 9, 1)], 4));
+    assert!(g.is_forbidden(7, 7));
+    assert!(!g.is_forbidden(7, 8));
+}
+
+#[test]
+fn renju_overline_is_forbidden_but_five_is_not() {
+    let g = renju_game(&with_corners(&[(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 7, 1), (7, 8, 1)], 5));
+    assert!(matches!(g.forbidden_reason(7, 6), Some(Forbidden::Overline { run }) if run.len() == 6));
+
+    // An exact five wins even if it also makes a double four
+    let g = renju_game(&with_corners(&[(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 6, 1), (4, 7, 1), (5, 7, 1), (6, 7, 1), (3, 7, 2)], 6));
+    assert!(!g.is_forbidden(7, 7));
+
+    // White has no restrictions
+    let mut g = renju_game(&with_corners(&[(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 7, 1), (7, 8, 1)], 5));
+    g.pass();
+    assert!(g.play(7, 6).is_ok());
+}
+
+#[test]
+fn symmetric_positions_share_tt_entries() {
+    let stones = [(7, 7, 1), (7, 8, 2), (8, 9, 1), (5, 6, 2), (9, 9, 1), (4, 10, 2)];
+    let g = game(&stones, BLACK);
+    let mut ai = AI::new(15, 15);
+    ai.tt_put(&g, TTEntry { depth: 3, score: 42, flag: TTFlag::Exact, best_move: Some((6, 8)) });
+
+    // Every rotation and reflection, the quarter turns included
+    for sym in 0..8 {
+        let turned: Vec<_> = stones
+            .iter()
+            .map(|&(row, col, player)| {
+                let (r, c) = g.zobrist.transform(sym, (row, col));
+                (r, c, player)
+            })
+            .collect();
+        let entry = ai.tt_get(&game(&turned, BLACK)).expect("same entry");
+        assert_eq!(entry.score, 42);
+        assert_eq!(entry.best_move, Some(g.zobrist.transform(sym, (6, 8))), "symmetry {sym}");
+    }
+    assert_eq!(ai.tt.len(), 1);
+    assert!(ai.tt_get(&game(&stones, WHITE)).is_none());
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+// Random moves on empty cells, undoing one now and then, until someone wins
+fn random_play(g: &mut Game, seed: u64, moves: usize, mut check: impl FnMut(&Game)) {
+    let mut state = seed;
+    for _ in 0..moves {
+        if g.winner().is_some() {
+            break;
+        }
+        if !g.moves().is_empty() && xorshift(&mut state).is_multiple_of(4) {
+            g.undo();
+        } else {
+            let empty: Vec<_> = (0..g.rows())
+                .flat_map(|r| (0..g.cols()).map(move |c| (r, c)))
+                .filter(|&(r, c)| g.board()[r][c] == EMPTY)
+                .collect();
+            let (row, col) = empty[(xorshift(&mut state) % empty.len() as u64) as usize];
+            g.play(row, col).unwrap();
+        }
+        check(g);
+    }
+}
+
+#[test]
+fn incremental_eval_matches_full_recompute() {
+    for (seed, rows, cols) in [(1, 15, 15), (7, 9, 13), (99, 6, 6)] {
+        let mut g = Game::from_board(vec![vec![EMPTY; cols]; rows], BLACK).unwrap();
+        random_play(&mut g, seed, 60, |g| {
+            for player in [BLACK, WHITE] {
+                assert_eq!(g.player_score(player), g.evaluate_player(player));
+            }
+        });
+    }
+}
+
+#[test]
+fn bitboard_agrees_with_array_scans() {
+    let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
+    for (seed, rows, cols) in [(3, 15, 15), (11, 8, 12), (5, 19, 19)] {
+        let mut g = Game::from_board(vec![vec![EMPTY; cols]; rows], BLACK).unwrap();
+        assert!(g.bits.is_some());
+        random_play(&mut g, seed, 80, |g| {
+            let mut arrays = g.clone();
+            arrays.set_bitboard(false);
+            for (row, col) in (0..rows).flat_map(|r| (0..cols).map(move |c| (r, c))) {
+                assert_eq!(g.five_direction(row, col), arrays.five_direction(row, col));
+                for player in [BLACK, WHITE] {
+                    assert_eq!(g.wins_at(row, col, player), arrays.wins_at(row, col, player));
+                    for (dr, dc) in dirs {
+                        assert_eq!(g.count_dir(row, col, dr, dc, player), arrays.count_dir(row, col, dr, dc, player));
+                        if g.board[row][col] == player {
+                            let line = g.eval_line(row, col, dr, dc, player);
+                            assert_eq!(line, arrays.eval_line(row, col, dr, dc, player), "({row}, {col}) going ({dr}, {dc})");
+                        }
+                    }
+                }
+            }
+            assert_eq!(g.winner(), arrays.winner());
+        });
+    }
+}
+
+#[test]
+fn play_and_undo_round_trip() {
+    let mut g = game(&[], BLACK);
+    let start = (g.zobrist.get_hash(), g.evaluate(&EvalConfig::default()));
+    let moves = [(7, 7), (7, 8), (8, 8), (0, 0)];
+
+    for (i, &(row, col)) in moves.iter().enumerate() {
+        assert_eq!(g.current_player(), if i % 2 == 0 { BLACK } else { WHITE });
+        g.play(row, col).unwrap();
+        assert_eq!(g.last_move(), Some((row, col)));
+    }
+    assert_eq!(g.moves(), moves);
+    assert_eq!(g.stone_count(), 4);
+
+    for &mv in moves.iter().rev() {
+        assert_eq!(g.undo(), Some(mv));
+    }
+    assert_eq!(g.undo(), None);
+    assert_eq!(g.current_player(), BLACK);
+    assert_eq!(g.stone_count(), 0);
+    assert_eq!((g.zobrist.get_hash(), g.evaluate(&EvalConfig::default())), start);
+}
+
+#[test]
+fn illegal_moves_are_rejected() {
+    let mut g = game(&[], BLACK);
+    g.play(7, 7).unwrap();
+    assert_eq!(g.play(7, 7), Err(MoveError::Occupied));
+    assert_eq!(g.play(15, 3), Err(MoveError::OutOfBounds));
+    assert_eq!(g.play(3, 15), Err(MoveError::OutOfBounds));
+    assert_eq!(g.moves(), [(7, 7)]);
+    assert_eq!(g.current_player(), WHITE);
+
+    let mut won = game(&[(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 6, 1), (0, 0, 2), (0, 2, 2), (0, 4, 2), (0, 6, 2)], BLACK);
+    won.play(7, 7).unwrap();
+    assert_eq!(won.play(1, 1), Err(MoveError::GameOver));
+    assert_eq!(GomokuError::from(MoveError::Occupied), GomokuError::IllegalMove { reason: MoveError::Occupied });
+}
+
+#[test]
+fn stray_cell_values_are_a_clean_error() {
+    let mut bad = board(15, &[(7, 7, 1)]);
+    bad[3][4] = 3;
+    let err = Game::from_board(bad.clone(), WHITE).err();
+    assert_eq!(err, Some(BoardError::InvalidCell { row: 3, col: 4, value: 3 }));
+    bad[3][4] = -1;
+    assert_eq!(check_board_shape(&bad, WHITE), Err(BoardError::InvalidCell { row: 3, col: 4, value: -1 }));
+    assert_eq!(validate_board(&bad, WHITE), Err(BoardError::InvalidCell { row: 3, col: 4, value: -1 }));
+
+    assert_eq!(Game::from_board(board(15, &[]), 0).err(), Some(BoardError::InvalidPlayer));
+    assert_eq!(Game::from_board(vec![vec![0; 15], vec![0; 14]], BLACK).err(), Some(BoardError::Ragged));
+    assert_eq!(
+        GomokuError::from(BoardError::InvalidPlayer),
+        GomokuError::InvalidBoard { reason: BoardError::InvalidPlayer }
+    );
+}
+
+#[test]
+fn exact_five_wins_but_overlines_only_in_freestyle() {
+    // Black fills (7, 6) next to four stones, making five; with one more
+    // stone at (7, 8) the same move makes six; and with four either side
+    // the five is buried in a run of seven.
+    let five = [(7, 2, 1), (7, 3, 1), (7, 4, 1), (7, 5, 1)];
+    let six = [(7, 2, 1), (7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 7, 1)];
+    let seven = [(7, 2, 1), (7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 7, 1), (7, 8, 1)];
+    for (stones, exact) in [(&five[..], true), (&six[..], false), (&seven[..], false)] {
+        for (allow, wins) in [(true, true), (false, exact)] {
+            let mut g = game(&with_corners(stones, stones.len()), BLACK);
+            g.set_allow_overline(allow);
+            assert_eq!(g.is_winning_move(7, 6, Cell::Black), Ok(wins), "{} stones, overline {allow}", stones.len());
+            g.play(7, 6).unwrap();
+            assert_eq!(g.winner(), wins.then_some(BLACK));
+            assert_eq!(g.five_on_board(), wins.then_some(BLACK));
+        }
+    }
+    let mut g = game(&[], BLACK);
+    assert!(g.is_five(5) && g.is_five(6));
+    g.set_allow_overline(false);
+    assert!(g.is_five(5) && !g.is_five(6) && !g.is_five(4));
+}
+
+#[test]
+fn rectangular_board_wins_past_the_short_side() {
+    // Ten rows by twenty columns, Black's five at columns 14 to 18
+    let mut stones = vec![vec![EMPTY; 20]; 10];
+    stones[3][14..18].fill(BLACK);
+    for col in [0, 2, 4, 6] {
+        stones[9][col] = WHITE;
+    }
+    let mut g = Game::from_board(stones, BLACK).unwrap();
+    assert_eq!((g.rows(), g.cols()), (10, 20));
+    // Either end of the four finishes it
+    let (row, col) = AI::new(10, 20).find_move(&g, 2).unwrap();
+    assert!([(3, 13), (3, 18)].contains(&(row, col)));
+    g.play(3, 18).unwrap();
+    assert_eq!(g.winner(), Some(BLACK));
+    assert_eq!(g.winning_line(), Some((14..19).map(|col| (3, col)).collect()));
+}
+
+#[test]
+fn swap2_takes_black_when_the_opening_favors_it() {
+    // Both black stones joined in the middle and White's stone thrown away
+    // in a corner
+    let g = game(&[(7, 7, 1), (7, 8, 1), (0, 0, 2)], WHITE);
+    assert_eq!(swap2_phase(&g), Swap2Phase::ChooseOption);
+    assert_eq!(AI::new(15, 15).swap2_choose_option(&g), Some(Swap2Option::Swap));
+}
+
+#[test]
+fn full_board_without_a_five_has_no_move() {
+    // Pairs of each color, shifted two columns every row
+    let full: Vec<Vec<i8>> = (0..5).map(|r| (0..5).map(|c| if (c + 2 * r) % 4 < 2 { BLACK } else { WHITE }).collect()).collect();
+    let g = Game::from_board(full, WHITE).unwrap();
+    assert!(g.is_full());
+    assert_eq!(g.five_on_board(), None);
+    assert_eq!(g.winner(), None);
+    let mut ai = AI::new(5, 5);
+    assert_eq!(ai.find_move(&g, 3), None);
+    assert_eq!(ai.find_move(&g, 0), None);
+}
+
+#[test]
+fn neighbor_radius_sets_the_candidate_ring() {
+    let g = game(&[(7, 7, 1)], WHITE);
+    let candidates = |neighbor_radius: usize| {
+        let config = AIConfig { neighbor_radius, max_candidates: 100, ..Default::default() };
+        let mut moves = AI::with_config(15, 15, config).get_ordered_moves_phase1(&g, 1, None);
+        moves.sort();
+        moves
+    };
+    // The square around the stone, less the stone itself
+    for radius in 1..=3 {
+        let moves = candidates(radius);
+        let side = 2 * radius + 1;
+        assert_eq!(moves.len(), side * side - 1, "radius {radius}");
+        assert!(moves.iter().all(|&(row, col)| row.abs_diff(7) <= radius && col.abs_diff(7) <= radius));
+    }
+    assert!(!candidates(1).contains(&(5, 5)));
+    assert!(candidates(2).contains(&(5, 5)));
+}
+
+#[test]
+fn opponent_four_is_blocked_in_every_orientation() {
+    // White's four on a row, a column and both diagonals, Black already on
+    // one end of it and to move
+    let fours = [
+        ([(7, 4), (7, 5), (7, 6), (7, 7)], (7, 3), (7, 8)),
+        ([(4, 7), (5, 7), (6, 7), (7, 7)], (3, 7), (8, 7)),
+        ([(4, 4), (5, 5), (6, 6), (7, 7)], (3, 3), (8, 8)),
+        ([(4, 10), (5, 9), (6, 8), (7, 7)], (3, 11), (8, 6)),
+    ];
+    for (stones, closed, open) in fours {
+        let mut position: Vec<_> = stones.iter().map(|&(row, col)| (row, col, WHITE)).collect();
+        position.extend([(closed.0, closed.1, BLACK), (0, 0, 1), (14, 14, 1)]);
+        let g = game(&position, BLACK);
+        let hash = g.zobrist.get_hash();
+
+        let mut ai = AI::new(15, 15);
+        assert_eq!(ai.immediate_move(&g).map(|(mv, _)| mv), Some(open));
+        assert_eq!(ai.ensure_block(&g, Some(((0, 1), 0))).map(|(mv, _)| mv), Some(open));
+        assert_eq!(ai.ensure_block(&g, Some((open, 7))), Some((open, 7)));
+        assert_eq!(ai.find_move(&g, 2), Some(open));
+        assert_eq!(g.zobrist.get_hash(), hash);
+
+        // Open at both ends there's no holding it
+        position.retain(|&(row, col, _)| (row, col) != closed);
+        position.push((1, 1, BLACK));
+        let g = game(&position, BLACK);
+        let (mv, score) = ai.immediate_move(&g).unwrap();
+        assert!([closed, open].contains(&mv));
+        assert!(is_losing_score(score));
+    }
+}
+
+#[test]
+fn aspiration_windows_match_the_full_window() {
+    let quiet = game(&MIDGAME, BLACK);
+    let tactical = game(&with_corners(&[(7, 5, 1), (7, 6, 1), (7, 7, 1), (8, 6, 2), (6, 6, 2)], 1), WHITE);
+    for g in [quiet, tactical] {
+        let mut full = AI::new(15, 15);
+        let mut expected = None;
+        for depth in 1..=4 {
+            expected = full.search_depth(&g, depth, -INF, INF, None);
+        }
+        assert_eq!(AI::new(15, 15).find_move_scored(&g, 4), expected);
+
+        // A guess far off on either side still widens to the same result
+        let (_, score) = expected.unwrap();
+        for guess in [score - 5000, score + 5000] {
+            let mut ai = AI::new(15, 15);
+            for depth in 1..4 {
+                ai.search_depth(&g, depth, -INF, INF, None);
+            }
+            assert_eq!(ai.aspiration_search(&g, 4, guess, None), expected, "guess {guess}");
+        }
+    }
+}
+
+#[test]
+fn saved_game_state_loads_back() {
+    let mut g = game(&[], BLACK);
+    for (row, col) in [(7, 7), (7, 8), (8, 8), (6, 6), (9, 9)] {
+        g.play(row, col).unwrap();
+    }
+    let json = serde_json::to_string(&g.to_state()).unwrap();
+    let state: GameState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state, g.to_state());
+
+    let mut loaded = Game::from_state(&state).unwrap();
+    assert_eq!(loaded.board(), g.board());
+    assert_eq!(loaded.moves(), g.moves());
+    assert_eq!(loaded.current_player(), WHITE);
+    assert_eq!(loaded.zobrist.get_hash(), g.zobrist.get_hash());
+    assert_eq!(loaded.undo(), Some((9, 9)));
+    assert_eq!(loaded.current_player(), BLACK);
+
+    let mut bad = state.clone();
+    bad.moves.push((0, 0));
+    assert!(Game::from_state(&bad).is_err());
+}
+
+#[test]
+fn saved_tt_loads_back_for_the_same_size_only() {
+    let path = std::env::temp_dir().join(format!("gomoku-tt-{}.json", std::process::id()));
+    let mut ai = AI::new(15, 15);
+    ai.find_move(&game(&MIDGAME, BLACK), 3);
+    ai.save_tt(&path).unwrap();
+
+    let mut loaded = AI::new(15, 15);
+    loaded.load_tt(&path).unwrap();
+    assert_eq!(loaded.tt.len(), ai.tt.len());
+    for (hash, entry) in &ai.tt {
+        let copy = loaded.tt[hash];
+        assert!(copy.depth == entry.depth && copy.score == entry.score && copy.flag == entry.flag && copy.best_move == entry.best_move);
+    }
+
+    let mut other = AI::new(13, 13);
+    assert!(other.load_tt(&path).is_err());
+    assert!(other.tt.is_empty());
+    std::fs::write(&path, "{").unwrap();
+    assert!(loaded.load_tt(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn late_move_reductions_save_nodes_and_keep_the_move() {
+    // Narrowed so the search gets deep enough to reduce in a test's time
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2)], BLACK);
+    let search = |late_move_reductions: bool| {
+        let config = AIConfig { late_move_reductions, max_candidates: 12, min_candidates: 6, ..Default::default() };
+        let (mv, stats) = AI::with_config(15, 15, config).find_move_with_stats(&g, 6);
+        (mv.unwrap(), stats.nodes)
+    };
+    let (full_move, full_nodes) = search(false);
+    let (reduced_move, reduced_nodes) = search(true);
+    assert_eq!(reduced_move, full_move);
+    assert!(reduced_nodes < full_nodes, "{reduced_nodes} nodes with reductions, {full_nodes} without");
+}
+
+#[test]
+fn double_three_scores_far_above_a_single_three() {
+    // Twos on row 7 and column 7: (7, 7) makes open threes on both,
+    // (7, 4) only on the row
+    let stones = with_corners(&[(7, 5, 1), (7, 6, 1), (5, 7, 1), (6, 7, 1)], 5);
+    let after = |row: usize, col: usize| {
+        let mut g = game(&stones, BLACK);
+        g.play(row, col).unwrap();
+        assert_eq!(g.player_score(BLACK), g.evaluate_player(BLACK));
+        (g.threat_count(BLACK), g.player_score(BLACK))
+    };
+    let (double_threats, double) = after(7, 7);
+    let (single_threats, single) = after(7, 4);
+    assert_eq!(double_threats, (2, 0));
+    assert_eq!(single_threats, (1, 0));
+    assert!(double - single >= DOUBLE_THREAT_BONUS, "{double} against {single}");
+}
+
+#[test]
+fn same_seed_same_moves() {
+    let g = game(&MIDGAME, BLACK);
+    let picks = |temperature: i32, seed: u64| {
+        let mut ai = AI::with_config(15, 15, AIConfig { temperature, seed: Some(seed), ..Default::default() });
+        (0..4)
+            .map(|_| {
+                ai.clear_tt();
+                ai.find_move(&g, 2).unwrap()
+            })
+            .collect::<Vec<_>>()
+    };
+    for seed in [1, 42] {
+        assert_eq!(picks(400, seed), picks(400, seed));
+    }
+    let picked: std::collections::HashSet<_> = (1..=5).flat_map(|seed| picks(400, seed)).collect();
+    assert!(picked.len() > 1);
+
+    // Off, the seed makes no difference
+    let best = AI::new(15, 15).find_move(&g, 2).unwrap();
+    assert!([1, 42].iter().all(|&seed| picks(0, seed).iter().all(|&mv| mv == best)));
+}
+
+#[test]
+fn threat_cells_mark_the_win_and_the_threes() {
+    // A four against the left edge, finished at (3, 4) and still a four
+    // past the gap from (3, 5), and a two on row 9 that becomes an open
+    // three from either side, with or without a gap
+    let g = game(&with_corners(&[(3, 0, 1), (3, 1, 1), (3, 2, 1), (3, 3, 1), (9, 6, 1), (9, 7, 1)], 6), BLACK);
+    let mut cells = AI::new(15, 15).threat_cells(&g);
+    cells.sort();
+    assert_eq!(
+        cells,
+        [
+            (3, 4, ThreatKind::Five),
+            (3, 5, ThreatKind::ClosedFour),
+            (9, 4, ThreatKind::OpenThree),
+            (9, 5, ThreatKind::OpenThree),
+            (9, 8, ThreatKind::OpenThree),
+            (9, 9, ThreatKind::OpenThree),
+        ]
+    );
+}
+
+#[test]
+fn three_scores_fall_as_its_ends_close() {
+    let three = [(7, 5, 1), (7, 6, 1), (7, 7, 1)];
+    let score = |blockers: &[(usize, usize, i8)]| {
+        let stones: Vec<_> = three.iter().chain(blockers).copied().collect();
+        let g = game(&stones, WHITE);
+        (g.eval_line(7, 5, 0, 1, BLACK).kind, g.player_score(BLACK))
+    };
+    let (open, open_score) = score(&[]);
+    let (closed, closed_score) = score(&[(7, 8, 2)]);
+    let (dead, dead_score) = score(&[(7, 4, 2), (7, 8, 2)]);
+    assert_eq!((open, closed, dead), (ThreatKind::OpenThree, ThreatKind::ClosedThree, ThreatKind::Other));
+    assert!(open_score > closed_score && closed_score > dead_score, "{open_score} {closed_score} {dead_score}");
+}
+
+#[test]
+fn pondering_saves_nodes_after_the_reply() {
+    // The AI plays White and ponders while Black thinks
+    let g = game(&MIDGAME, BLACK);
+    let mut ai = AI::new(15, 15);
+    ai.start_ponder(&g);
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    ai.stop_ponder();
+    assert!(!ai.tt.is_empty());
+
+    let mut after = g.clone();
+    let (row, col) = AI::new(15, 15).find_move(&g, 2).unwrap();
+    after.play(row, col).unwrap();
+    let (warm_move, warm) = ai.find_move_with_stats(&after, 4);
+    let (cold_move, cold) = AI::new(15, 15).find_move_with_stats(&after, 4);
+    assert_eq!(warm_move, cold_move);
+    assert!(warm.nodes < cold.nodes, "{} nodes after pondering, {} cold", warm.nodes, cold.nodes);
+}
+
+#[test]
+fn search_stats_count_nodes_and_table_hits() {
+    let g = game(&MIDGAME, BLACK);
+    let mut hits = 0;
+    for depth in 1..=4 {
+        let (mv, stats) = AI::new(15, 15).find_move_with_stats(&g, depth);
+        assert!(mv.is_some());
+        assert!(stats.nodes > 0);
+        assert_eq!(stats.max_depth, depth);
+        // Each iteration finds the ones before it in the table
+        assert!(depth == 1 || stats.tt_hits > hits, "{} hits at depth {depth}", stats.tt_hits);
+        hits = stats.tt_hits;
+    }
+
+    // Searching again starts from the table the first search filled
+    let mut ai = AI::new(15, 15);
+    let (_, first) = ai.find_move_with_stats(&g, 4);
+    let (_, again) = ai.find_move_with_stats(&g, 4);
+    assert!(again.tt_hits > 0);
+    assert!(again.nodes < first.nodes);
+}
+
+#[test]
+fn history_is_kept_per_side() {
+    let g = game(&MIDGAME, BLACK);
+    let mut ai = AI::new(15, 15);
+    ai.find_move(&g, 4);
+    let total = |side: usize| ai.history[side].iter().flatten().sum::<i32>();
+    assert!(total(0) > 0 && total(1) > 0);
+    assert_ne!(ai.history[0], ai.history[1]);
+
+    // Only the side to move's table reorders its moves
+    let mut ai = AI::with_config(15, 15, AIConfig { max_candidates: 100, ..Default::default() });
+    let before = ai.get_ordered_moves_phase1(&g, 1, None);
+    let (row, col) = *before.last().unwrap();
+    ai.history[1][row][col] = 1_000_000;
+    assert_eq!(ai.get_ordered_moves_phase1(&g, 1, None), before);
+    ai.history[0][row][col] = 1_000_000;
+    assert_eq!(ai.get_ordered_moves_phase1(&g, 1, None)[0], (row, col));
+}
+
+#[test]
+fn win_length_four_and_six() {
+    // Four in a row: an open three is already a win for the side to move,
+    // and lost for the side that isn't
+    let three = [(7, 5, 1), (7, 6, 1), (7, 7, 1)];
+    let mut g = game(&with_corners(&three, 3), BLACK);
+    g.set_win_length(4);
+    let (mv, score) = AI::new(15, 15).find_move_scored(&g, 3).unwrap();
+    assert!([(7, 4), (7, 8)].contains(&mv));
+    assert_eq!(score, WIN);
+    g.play(mv.0, mv.1).unwrap();
+    assert_eq!(g.winner(), Some(BLACK));
+
+    let mut g = game(&with_corners(&three, 3), WHITE);
+    g.set_win_length(4);
+    let (mv, score) = AI::new(15, 15).find_move_scored(&g, 3).unwrap();
+    assert!([(7, 4), (7, 8)].contains(&mv));
+    assert!(is_losing_score(score));
+
+    // Six in a row: five stones don't win yet, but open at both ends
+    // they're what an open four is to five
+    let five = [(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 6, 1), (7, 7, 1)];
+    let mut g = game(&with_corners(&five, 5), WHITE);
+    g.set_win_length(6);
+    assert_eq!(g.five_on_board(), None);
+    assert!(g.is_winning_move(7, 8, Cell::Black).unwrap() && !g.is_winning_move(7, 9, Cell::Black).unwrap());
+    for player in [BLACK, WHITE] {
+        assert_eq!(g.player_score(player), g.evaluate_player(player));
+    }
+    let (block, score) = AI::new(15, 15).find_move_scored(&g, 3).unwrap();
+    assert!([(7, 2), (7, 8)].contains(&block));
+    assert!(is_losing_score(score));
+    g.play(7, 8).unwrap();
+    assert_eq!(g.winner(), None);
+    g.play(7, 2).unwrap();
+    assert_eq!(g.winner(), Some(BLACK));
+}
+
+#[test]
+fn threat_profile_counts_each_line_once() {
+    // An open four on row 7, a closed four on column 2, and a broken three
+    // on the diagonal; four, four and three stones that could each claim
+    // their line
+    let stones = [
+        (7, 5, 1),
+        (7, 6, 1),
+        (7, 7, 1),
+        (7, 8, 1),
+        (1, 2, 1),
+        (2, 2, 1),
+        (3, 2, 1),
+        (4, 2, 1),
+        (0, 2, 2),
+        (10, 9, 1),
+        (11, 10, 1),
+        (13, 12, 1),
+    ];
+    let g = game(&stones, WHITE);
+    let profile = g.threat_profile(Cell::Black);
+    assert_eq!(profile, ThreatProfile { fives: 0, open_fours: 1, fours: 1, open_threes: 0, broken_threes: 1 });
+    for (row, col) in [(7, 5), (7, 6), (7, 7), (7, 8)] {
+        assert_eq!(g.starts_line(row, col, 0, 1, BLACK), col == 5);
+    }
+    assert_eq!(g.threat_count(BLACK), (3, 2));
+
+    // Turned or reflected, so the lines are scanned from their other ends
+    for sym in 1..8 {
+        let turned: Vec<_> = stones
+            .iter()
+            .map(|&(row, col, player)| {
+                let (r, c) = g.zobrist.transform(sym, (row, col));
+                (r, c, player)
+            })
+            .collect();
+        assert_eq!(game(&turned, WHITE).threat_profile(Cell::Black), profile, "symmetry {sym}");
+    }
+}
+
+#[test]
+fn analysis_flags_the_blunder() {
+    // White answers Black's open three on row 7 in the corner, then Black
+    // makes an open four and five
+    let moves = [(7, 7), (8, 8), (7, 8), (8, 9), (7, 9), (14, 14), (7, 10), (0, 0), (7, 11)];
+    let analysis = AI::new(15, 15).analyze_game(&moves, 15);
+    assert_eq!(analysis.len(), moves.len());
+    assert!(analysis.iter().zip(moves).all(|(a, (row, col))| (a.row, a.col) == (row, col)));
+
+    let blunder = &analysis[5];
+    assert_eq!(blunder.player, WHITE);
+    assert!(blunder.blunder);
+    assert!(matches!(blunder.best, Some((7, 6)) | Some((7, 10))), "{:?}", blunder.best);
+    // The one move that threw the game away. Black's were all fine, and
+    // White's last was lost anyway.
+    let lost = |a: &MoveAnalysis| is_losing_score(a.eval_after) && !is_losing_score(a.eval_before);
+    assert_eq!(analysis.iter().position(lost), Some(5));
+    assert_eq!(analysis.iter().filter(|&a| lost(a)).count(), 1);
+    assert!(analysis.iter().filter(|a| a.player == BLACK).all(|a| !a.blunder && a.best.is_none()));
+}
+
+#[test]
+fn opponent_weight_tips_attack_or_block() {
+    // Black and White each have an open two, far apart: Black can make its
+    // own three or stop White's, which score the same at equal weights
+    let g = game(&[(7, 7, 1), (7, 8, 1), (3, 3, 2), (3, 4, 2), (11, 11, 1), (0, 14, 2)], BLACK);
+    let scored = |opponent_weight: f32| {
+        let config = AIConfig { eval: EvalConfig { opponent_weight, ..Default::default() }, ..Default::default() };
+        AI::with_config(15, 15, config).scored_moves(&g)
+    };
+    let score_of = |moves: &[((usize, usize), i32)], cell| moves.iter().find(|&&(mv, _)| mv == cell).unwrap().1;
+    let equal = scored(1.0);
+    assert_eq!(score_of(&equal, (7, 6)), score_of(&equal, (3, 2)));
+
+    for w in [0.5, 0.8] {
+        assert!([(7, 6), (7, 9)].contains(&scored(w)[0].0), "weight {w}");
+    }
+    for w in [1.1, 1.5, 3.0] {
+        assert!([(3, 2), (3, 5)].contains(&scored(w)[0].0), "weight {w}");
+    }
+}
+
+#[test]
+fn top_scored_move_is_searched_first() {
+    let g = game(&MIDGAME, BLACK);
+    // Without the bonus for cells next to White's longest line, ordering is
+    // score_move alone on a fresh AI
+    let config = AIConfig { threat_proximity: false, trace_depth: 1, ..Default::default() };
+    let mut ai = AI::with_config(15, 15, config);
+    let scored = ai.scored_moves(&g);
+    assert!(scored.len() > config.max_candidates);
+    assert!(scored.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+
+    let (top, _) = scored[0];
+    assert_eq!(ai.get_ordered_moves_phase1(&g, 0, None)[0], top);
+    ai.find_move(&g, 1).unwrap();
+    assert_eq!(ai.trace()[0].mv, top);
+}
+
+#[test]
+fn even_board_opens_centrally_without_bias() {
+    // 14x14 has four central cells; the upper-left one is the opening
+    let empty = Game::from_board(board(14, &[]), BLACK).unwrap();
+    assert_eq!(empty.center(), (6, 6));
+    let mut ai = AI::new(14, 14);
+    assert!(ai.killer_moves.iter().flatten().all(|killer| killer.is_none()));
+    assert_eq!(ai.find_move(&empty, 4), Some((6, 6)));
+
+    // A position and its half turn about the middle of the board get the
+    // same move, turned
+    let stones = [(6, 6, 1), (6, 7, 2), (7, 7, 1)];
+    let turned: Vec<_> = stones.iter().map(|&(row, col, player)| (13 - row, 13 - col, player)).collect();
+    let (mv, score) = AI::new(14, 14).find_move_scored(&Game::from_board(board(14, &stones), WHITE).unwrap(), 4).unwrap();
+    let (turned_mv, turned_score) = AI::new(14, 14).find_move_scored(&Game::from_board(board(14, &turned), WHITE).unwrap(), 4).unwrap();
+    assert_eq!((13 - mv.0, 13 - mv.1), turned_mv);
+    assert_eq!(score, turned_score);
+}
+
+#[test]
+fn diagram_round_trip() {
+    let diagram = "
+        . . . . . .
+        . X X X X X
+        . O O O O .
+        . . . . . .
+        . . . . . .
+    ";
+    let g = Game::from_diagram(diagram, WHITE).unwrap();
+    assert_eq!((g.rows(), g.cols()), (5, 6));
+    assert_eq!(g.five_on_board(), Some(BLACK));
+    assert!(g.five_direction(1, 3).is_some() && g.five_direction(2, 2).is_none());
+    let text = g.to_diagram();
+    assert_eq!(text, "......\n.XXXXX\n.OOOO.\n......\n......\n");
+    assert_eq!(Game::from_diagram(&text, WHITE).unwrap().board(), g.board());
+
+    let g = game(&MIDGAME, BLACK);
+    let back = Game::from_diagram(&g.to_diagram(), BLACK).unwrap();
+    assert_eq!(back.board(), g.board());
+    assert_eq!(back.zobrist.get_hash(), g.zobrist.get_hash());
+
+    assert_eq!(Game::from_diagram("..\n.#", BLACK).err(), Some(BoardError::InvalidSymbol { row: 1, col: 1, symbol: '#' }));
+    assert_eq!(Game::from_diagram("..\n.", BLACK).err(), Some(BoardError::Ragged));
+}
+
+#[test]
+fn resigns_against_an_open_four() {
+    let four = [(7, 5, 1), (7, 6, 1), (7, 7, 1), (7, 8, 1)];
+    let g = game(&with_corners(&four, 3), WHITE);
+    assert_eq!(AI::new(15, 15).find_move_or_resign(&g, 4), Some(Decision::Resign));
+    let never = AIConfig { resign_threshold: None, ..Default::default() };
+    assert!(matches!(AI::with_config(15, 15, never).find_move_or_resign(&g, 4), Some(Decision::Play(mv, score)) if [(7, 4), (7, 9)].contains(&mv) && is_losing_score(score)));
+
+    // One end already shut is a block, not a loss
+    let g = game(&with_corners(&[&four[..], &[(7, 4, 2)]].concat(), 2), WHITE);
+    assert!(matches!(AI::new(15, 15).find_move_or_resign(&g, 4), Some(Decision::Play((7, 9), score)) if !is_losing_score(score)));
+}
+
+#[test]
+fn faster_wins_score_higher() {
+    // Black can finish the four on row 10 now, or play (5, 7) for open
+    // threes on row 5 and column 7 and win two moves later
+    let g = game(
+        &[(10, 0, 1), (10, 1, 1), (10, 2, 1), (10, 3, 1), (5, 5, 1), (5, 6, 1), (6, 7, 1), (7, 7, 1), (0, 10, 2), (0, 12, 2), (14, 14, 2), (14, 0, 2), (12, 12, 2)],
+        BLACK,
+    );
+    let config = AIConfig { vcf: false, vct: false, ..Default::default() };
+    let mut ai = AI::with_config(15, 15, config);
+    assert_eq!(ai.root_score(&g, (10, 4), 5), WIN);
+    assert_eq!(ai.root_score(&g, (5, 7), 5), WIN - 4);
+    // Straight from the search, without the check for an immediate five
+    assert_eq!(ai.search_depth(&g, 5, -INF, INF, None), Some(((10, 4), WIN)));
+
+    // And the loser puts the loss off as long as it can: one move against
+    // an open four
+    let g = game(&with_corners(&[(7, 5, 1), (7, 6, 1), (7, 7, 1), (7, 8, 1)], 3), WHITE);
+    let (_, score) = AI::new(15, 15).find_move_scored(&g, 4).unwrap();
+    assert_eq!(score, -(WIN - 1));
+}
+
+#[test]
+fn book_openings_are_legal_and_central() {
+    let opening = |seed: u64, renju: bool| {
+        let mut g = game(&[], BLACK);
+        g.set_renju_mode(renju);
+        let mut ai = AI::with_config(15, 15, AIConfig { opening_moves: 3, seed: Some(seed), ..Default::default() });
+        (0..3)
+            .map(|_| {
+                let (row, col) = ai.find_move(&g, 2).unwrap();
+                assert!(row.abs_diff(7) <= 1 && col.abs_diff(7) <= 1, "({row}, {col}) with seed {seed}");
+                g.play(row, col).unwrap();
+                (row, col)
+            })
+            .collect::<Vec<_>>()
+    };
+    let lines: std::collections::HashSet<_> = (0..20).map(|seed| opening(seed, false)).collect();
+    assert!(lines.len() > 5, "{} openings in 20 games", lines.len());
+    assert_eq!(opening(9, false), opening(9, false));
+    for seed in 0..5 {
+        opening(seed, true);
+    }
+
+    // Off the book, the search takes over
+    let off_book = game(&[(7, 7, 1), (3, 3, 2)], BLACK);
+    assert_eq!(AI::with_config(15, 15, AIConfig { opening_moves: 3, ..Default::default() }).book_move(&off_book), None);
+}
+
+#[test]
+fn open_four_evaluates_strongly_for_its_owner() {
+    let four = with_corners(&[(7, 5, 1), (7, 6, 1), (7, 7, 1), (7, 8, 1)], 3);
+    let eval = EvalConfig::default();
+    let black = game(&four, BLACK);
+    assert!(black.player_score(BLACK) > 10 * black.player_score(WHITE));
+    assert!(black.evaluate(&eval) > 5000, "{}", black.evaluate(&eval));
+    // Seen from White's side it's as bad
+    let white = game(&four, WHITE);
+    assert!(white.evaluate(&eval) < -5000, "{}", white.evaluate(&eval));
+}
+
+#[test]
+fn open_three_scores_once_from_any_stone() {
+    let lone = game(&[(7, 6, 1)], WHITE);
+    // A lone stone is four one-stone lines
+    let single_line = lone.evaluate_player(BLACK) / 4;
+
+    // Solid and broken, across the row, down the column and on a diagonal
+    let threes = [
+        [(7, 5), (7, 6), (7, 7)],
+        [(4, 9), (5, 9), (6, 9)],
+        [(3, 3), (4, 4), (5, 5)],
+        [(7, 4), (7, 6), (7, 7)],
+    ];
+    for stones in threes {
+        let position: Vec<_> = stones.iter().map(|&(row, col)| (row, col, BLACK)).collect();
+        let g = game(&position, WHITE);
+        let (dr, dc) = ((stones[1].0 - stones[0].0).min(1) as i32, (stones[1].1 - stones[0].1).min(1) as i32);
+        let kind = g.eval_line(stones[0].0, stones[0].1, dr, dc, BLACK).kind;
+        assert!(matches!(kind, ThreatKind::OpenThree | ThreatKind::BrokenThree));
+
+        // Only the first stone scores the line, whichever stone is asked
+        let from: Vec<_> = stones.iter().map(|&(row, col)| g.eval_dir(row, col, dr, dc, BLACK)).collect();
+        assert_eq!(from, [g.patterns.score(kind), 0, 0]);
+        assert_eq!(g.evaluate_player(BLACK), g.patterns.score(kind) + 3 * 3 * single_line);
+        assert_eq!(g.player_score(BLACK), g.evaluate_player(BLACK));
+        assert_eq!(g.threat_count(BLACK), (1, 0));
+    }
+}
+
+#[test]
+fn same_seed_same_zobrist_keys() {
+    let keys = ZobristHash::with_seed(15, 15, 42);
+    let again = ZobristHash::with_seed(15, 15, 42);
+    assert!(keys.table == again.table && keys.side == again.side);
+    assert!(ZobristHash::with_seed(15, 15, 43).table != keys.table);
+
+    // No two keys collide
+    let mut all: Vec<u64> = keys.table.iter().flatten().flatten().copied().collect();
+    all.push(keys.side);
+    let count = all.len();
+    all.sort_unstable();
+    all.dedup();
+    assert_eq!(all.len(), count);
+
+    // So games built separately hash alike
+    let stones = [(7, 7, 1), (7, 8, 2)];
+    assert_eq!(game(&stones, BLACK).zobrist.get_hash(), game(&stones, BLACK).zobrist.get_hash());
+    assert_eq!(ZobristHash::new(15, 15).table, ZobristHash::with_seed(15, 15, ZOBRIST_SEED).table);
+}
+
+#[test]
+fn tablebase_plays_4x4_perfectly() {
+    let mut ai = AI::new(4, 4);
+    assert!(ai.build_tablebase(6, 4).unwrap_err().contains("4x4"));
+    // Three in a row, so the solve stays quick. Black wins it.
+    ai.build_tablebase(4, 3).unwrap();
+
+    let mut g = Game::from_board(vec![vec![EMPTY; 4]; 4], BLACK).unwrap();
+    g.set_win_length(3);
+    assert!(ai.find_move_scored(&g, 1).unwrap().1 > WIN_THRESHOLD);
+    while g.winner().is_none() {
+        let (row, col) = ai.find_move(&g, 1).unwrap();
+        g.play(row, col).unwrap();
+    }
+    assert_eq!(g.winner(), Some(BLACK));
+
+    // White to move loses however it plays, and a win in one scores as one
+    let mut lost = Game::from_diagram("X...
+....
+....
+....", WHITE).unwrap();
+    lost.set_win_length(3);
+    assert!(ai.find_move_scored(&lost, 1).unwrap().1 < -WIN_THRESHOLD);
+    let mut won = Game::from_diagram("XX..
+O...
+O...
+....", BLACK).unwrap();
+    won.set_win_length(3);
+    assert_eq!(ai.find_move_scored(&won, 1), Some(((0, 2), WIN)));
+}
+
+#[test]
+fn multi_pv_leads_with_the_best_move() {
+    // Openings grown from the ordering's top few moves
+    let mut state = 555;
+    for stones in 4..8 {
+        let mut g = game(&[(7, 7, 1)], WHITE);
+        for _ in 0..stones {
+            let moves = AI::new(15, 15).get_ordered_moves_phase1(&g, 0, None);
+            let (row, col) = moves[(xorshift(&mut state) % 5) as usize % moves.len()];
+            g.play(row, col).unwrap();
+        }
+        for depth in [2, 3] {
+            let best = AI::new(15, 15).find_move_scored(&g, depth).unwrap();
+            let lines = AI::new(15, 15).find_moves_multi(&g, depth, 5);
+            assert_eq!(lines.len(), 5);
+            assert_eq!(lines[0], best);
+            assert!(lines.windows(2).all(|pair| pair[0].1 >= pair[1].1), "{lines:?} at depth {depth}");
+        }
+    }
+}
+
+#[test]
+fn won_boards_are_rejected() {
+    // Balanced and White to move, but Black already has five
+    let won = board(15, &[(1, 1, 1), (1, 2, 1), (1, 3, 1), (1, 4, 1), (1, 5, 1), (2, 1, 2), (2, 2, 2), (2, 3, 2), (2, 4, 2)]);
+    assert_eq!(validate_board(&won, WHITE), Err(BoardError::AlreadyWon { winner: BLACK }));
+    assert_eq!(GomokuError::from(BoardError::AlreadyWon { winner: BLACK }), GomokuError::GameOver { winner: BLACK });
+    assert_eq!(Game::from_board(won, WHITE).unwrap().five_on_board(), Some(BLACK));
+    assert_eq!(validate_board(&board(15, &[(7, 7, 1)]), WHITE), Ok(()));
+
+    // An overline only counts where overlines win
+    let mut six = Game::from_diagram(".XXXXXX.\n........\nOOOOO...", WHITE).unwrap();
+    assert_eq!(six.five_on_board(), Some(BLACK));
+    six.set_allow_overline(false);
+    assert_eq!(six.five_on_board(), Some(WHITE));
+}
+
+#[test]
+fn winning_line_lists_the_five_in_order() {
+    // Completed in the middle of a diagonal
+    let mut g = game(&[(3, 3, 1), (4, 4, 1), (6, 6, 1), (7, 7, 1), (0, 0, 2), (0, 1, 2), (0, 2, 2), (0, 3, 2)], BLACK);
+    assert_eq!(g.winning_line(), None);
+    g.play(5, 5).unwrap();
+    assert_eq!(g.winning_line(), Some(vec![(3, 3), (4, 4), (5, 5), (6, 6), (7, 7)]));
+
+    // Completed at the top end of an anti-diagonal
+    let mut g = game(&[(1, 5, 2), (2, 4, 2), (3, 3, 2), (4, 2, 2), (9, 9, 1), (9, 10, 1), (9, 11, 1), (10, 3, 1), (12, 2, 1)], WHITE);
+    g.play(0, 6).unwrap();
+    assert_eq!(g.winning_line(), Some(vec![(0, 6), (1, 5), (2, 4), (3, 3), (4, 2)]));
+
+    // A freestyle overline is all of it
+    let mut g = game(&[(7, 2, 1), (7, 3, 1), (7, 5, 1), (7, 6, 1), (7, 7, 1), (0, 0, 2), (0, 2, 2), (0, 4, 2), (0, 6, 2)], BLACK);
+    g.play(7, 4).unwrap();
+    assert_eq!(g.winning_line(), Some((2..8).map(|col| (7, col)).collect()));
+}
+
+#[test]
+fn first_player_penalty_evens_out_a_mirrored_position() {
+    // White's three mirrors Black's across row 7, Black has the extra stone
+    // moving first gives
+    let g = game(&[(6, 6, 1), (6, 7, 1), (6, 8, 1), (8, 6, 2), (8, 7, 2), (8, 8, 2), (7, 3, 1)], WHITE);
+    let plain = g.evaluate(&EvalConfig::default());
+    let fair = g.evaluate(&EvalConfig { first_player_penalty: 0.1, ..Default::default() });
+    assert!(plain < 0, "{plain}");
+    assert!(fair.abs() < plain.abs(), "{fair} against {plain}");
+}
+
+#[test]
+fn threat_proximity_orders_the_block_first() {
+    // White's open three on row 7 against Black's development top left
+    let g = game(
+        &[(7, 5, 2), (7, 6, 2), (7, 7, 2), (14, 0, 2), (14, 14, 2), (2, 2, 1), (2, 3, 1), (3, 4, 1), (4, 4, 1), (12, 12, 1)],
+        BLACK,
+    );
+    assert_eq!(g.longest_run(WHITE), vec![(7, 5), (7, 6), (7, 7)]);
+    let ranks = |flag| {
+        let ai = AI::with_config(15, 15, AIConfig { threat_proximity: flag, ..Default::default() });
+        let moves = ai.get_ordered_moves_phase1(&g, 0, None);
+        [(7, 4), (2, 4), (6, 4), (3, 3)].map(|mv| moves.iter().position(|&m| m == mv).unwrap())
+    };
+
+    let [block, develop, near, far] = ranks(true);
+    assert_eq!(block, 0);
+    assert!(block < develop);
+    // Touching the three lifts (6, 4) over the development at (3, 3)
+    assert!(near < far);
+    let [_, _, near, far] = ranks(false);
+    assert!(near > far);
+}
+
+#[test]
+fn loaded_table_saves_nodes_on_the_same_position() {
+    let g = game(&MIDGAME, BLACK);
+    let path = std::env::temp_dir().join(format!("gomoku-warm-tt-{}.json", std::process::id()));
+    let mut cold = AI::new(15, 15);
+    let (cold_move, cold_stats) = cold.find_move_with_stats(&g, 4);
+    cold.save_tt(&path).unwrap();
+
+    let mut warm = AI::new(15, 15);
+    warm.load_tt(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let (warm_move, warm_stats) = warm.find_move_with_stats(&g, 4);
+    assert_eq!(warm_move, cold_move);
+    assert!(warm_stats.nodes < cold_stats.nodes, "{} nodes warm, {} cold", warm_stats.nodes, cold_stats.nodes);
+}
+
+#[test]
+fn self_play_finishes_and_repeats_with_a_seed() {
+    let config = AIConfig { seed: Some(3), temperature: 200, ..Default::default() };
+    let record = self_play(9, 1, 2, config).unwrap();
+
+    // Legal throughout, and over exactly when the record ends
+    let mut g = Game::from_board(vec![vec![EMPTY; 9]; 9], BLACK).unwrap();
+    for &(row, col) in &record.moves {
+        assert_eq!(g.winner(), None);
+        g.play(row, col).unwrap();
+    }
+    assert!(g.winner().is_some() || g.is_full());
+    assert_eq!(g.winner(), record.winner);
+
+    assert_eq!(self_play(9, 1, 2, config).unwrap(), record);
+    assert!(self_play(0, 2, 2, config).is_err());
+}
+
+#[test]
+fn forbidden_reason_names_the_cells_of_each_line() {
+    let g = renju_game(&with_corners(&[(7, 5, 1), (7, 6, 1), (5, 7, 1), (6, 7, 1)], 4));
+    let threes = vec![vec![(7, 5), (7, 6), (7, 7)], vec![(5, 7), (6, 7), (7, 7)]];
+    assert_eq!(g.forbidden_reason(7, 7), Some(Forbidden::DoubleThree { threes }));
+
+    // A broken three counts with its gap left out
+    let g = renju_game(&with_corners(&[(4, 4, 1), (6, 6, 1), (7, 8, 1), (7, 9, 1)], 4));
+    let threes = vec![vec![(7, 7), (7, 8), (7, 9)], vec![(4, 4), (6, 6), (7, 7)]];
+    assert_eq!(g.forbidden_reason(7, 7), Some(Forbidden::DoubleThree { threes }));
+
+    // X_XXX_X holds two fours on one line
+    let g = renju_game(&with_corners(&[(5, 2, 1), (5, 4, 1), (5, 6, 1), (5, 8, 1)], 4));
+    let fours = vec![vec![(5, 2), (5, 4), (5, 5), (5, 6)], vec![(5, 4), (5, 5), (5, 6), (5, 8)]];
+    assert_eq!(g.forbidden_reason(5, 5), Some(Forbidden::DoubleFour { fours }));
+    assert_eq!(g.forbidden_reason(10, 10), None);
+
+    let g = renju_game(&with_corners(&[(3, 0, 1), (3, 1, 1), (3, 2, 1), (3, 4, 1), (3, 5, 1)], 5));
+    assert_eq!(g.forbidden_reason(3, 3), Some(Forbidden::Overline { run: (0..6).map(|col| (3, col)).collect() }));
+}
+
+#[test]
+fn root_table_move_is_tried_first_next_iteration() {
+    // Openings grown from the ordering's top few moves
+    let mut state = 11;
+    let (mut nodes, mut nodes_without) = (0, 0);
+    for _ in 0..8 {
+        let mut g = game(&[], BLACK);
+        for _ in 0..6 + xorshift(&mut state) % 6 {
+            let moves = AI::new(15, 15).scored_moves(&g);
+            let ((row, col), _) = moves[(xorshift(&mut state) % 4) as usize % moves.len()];
+            g.play(row, col).unwrap();
+        }
+        if g.winner().is_some() {
+            continue;
+        }
+
+        // The same table after depth 3, with and without the root's entry
+        let config = AIConfig { vcf: false, ..Default::default() };
+        let (mut with, mut without) = (AI::with_config(15, 15, config), AI::with_config(15, 15, config));
+        with.find_move_scored(&g, 3);
+        without.find_move_scored(&g, 3);
+        without.tt.remove(&g.canonical_hash().0);
+        for (ai, total) in [(&mut with, &mut nodes), (&mut without, &mut nodes_without)] {
+            ai.stats.nodes = 0;
+            ai.search_depth(&g, 4, -INF, INF, None);
+            *total += ai.stats.nodes;
+        }
+    }
+    assert!(nodes < nodes_without, "{nodes} nodes with the root move, {nodes_without} without");
+}
+
+#[test]
+fn depth_zero_plays_the_top_ordered_move() {
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1)], WHITE);
+    let mut ai = AI::new(15, 15);
+    let top = ai.get_ordered_moves_phase1(&g, 0, None);
+    let expected = ai.instant_move(&g, &top);
+    for result in [ai.find_move_scored(&g, 0), ai.find_move_parallel(&g, 0, 3)] {
+        assert_eq!(result, expected);
+        let ((row, col), _) = result.unwrap();
+        assert_eq!(g.board()[row][col], EMPTY);
+        assert!(g.has_neighbor(row, col, 1));
+    }
+    assert_eq!(AI::new(15, 15).find_move(&game(&[], BLACK), 0), Some((7, 7)));
+}
+
+#[test]
+fn must_block_overrides_a_move_that_loses_at_once() {
+    // White's four on row 3 is closed at (3, 2), Black to move
+    let g = game(&[(3, 3, 2), (3, 4, 2), (3, 5, 2), (3, 6, 2), (3, 2, 1), (7, 7, 1), (8, 8, 1), (9, 9, 1)], BLACK);
+    // A search result that missed the block
+    let missed = Some(((10, 10), 500));
+    assert_eq!(AI::new(15, 15).ensure_block(&g, missed).map(|(mv, _)| mv), Some((3, 7)));
+    assert_eq!(AI::with_config(15, 15, AIConfig { must_block: false, ..Default::default() }).ensure_block(&g, missed), missed);
+
+    // A five of Black's own beats blocking
+    let g = game(&[(3, 3, 2), (3, 4, 2), (3, 5, 2), (3, 6, 2), (14, 0, 2), (3, 2, 1), (6, 6, 1), (7, 7, 1), (8, 8, 1), (9, 9, 1)], BLACK);
+    let win = Some(((10, 10), WIN));
+    assert_eq!(AI::new(15, 15).ensure_block(&g, win), win);
+}
+
+#[test]
+fn progress_reports_each_depth_and_ends_like_find_move() {
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1)], WHITE);
+    let mut seen = Vec::new();
+    let result = AI::new(15, 15).find_move_with_progress(&g, 4, |progress| seen.push(progress)).unwrap();
+    assert_eq!(seen.iter().map(|progress| progress.depth).collect::<Vec<_>>(), [1, 2, 3, 4]);
+    assert_eq!((seen[3].best_move, seen[3].score), result);
+    assert_eq!(AI::new(15, 15).find_move_scored(&g, 4), Some(result));
+
+    // Lost, with a closed three on row 12 for White to hold out with and
+    // too few root candidates for the plain search to see it
+    let lost = game(
+        &[(7, 5, 1), (7, 6, 1), (7, 7, 1), (4, 10, 1), (5, 10, 1), (6, 10, 1), (12, 1, 1), (12, 2, 2), (12, 3, 2), (12, 4, 2), (0, 0, 2), (0, 14, 2), (14, 14, 2)],
+        WHITE,
+    );
+    let config = AIConfig { max_candidates: 5, min_candidates: 5, resign_threshold: None, longest_defense: true, ..Default::default() };
+    let longest = AI::with_config(15, 15, config).find_move_scored(&lost, 4).unwrap();
+    let plain = AI::with_config(15, 15, AIConfig { longest_defense: false, ..config }).find_move_scored(&lost, 4).unwrap();
+    assert!(longest.1 > plain.1);
+    assert_eq!(AI::with_config(15, 15, config).find_move_with_progress(&lost, 4, |_| {}), Some(longest));
+}
+
+#[test]
+fn eval_and_move_ordering_score_from_one_table() {
+    // Nothing scores but an open three, at a value of the test's choosing
+    for open_three in [777, 1234] {
+        let patterns = PatternTable {
+            five: 0,
+            open_four: 0,
+            four: 0,
+            dead_four: 0,
+            open_three,
+            broken_three: 0,
+            closed_three: 0,
+            open_two: 0,
+            split_two: 0,
+            closed_two: 0,
+            other: 0,
+        };
+        let mut g = game(&with_corners(&[(7, 6, 1), (7, 7, 1)], 2), BLACK);
+        g.set_patterns(patterns);
+        // Weighed the same as a move and once on the board
+        assert_eq!(g.score_move(7, 8, &EvalConfig::default()), open_three);
+        g.play(7, 8).unwrap();
+        assert_eq!(g.evaluate_player(BLACK), open_three);
+        assert_eq!(g.player_score(BLACK), open_three);
+    }
+}
+
+#[test]
+fn four_three_fork_is_chosen() {
+    // A closed three on row 7 and a two on column 6: (7, 6) makes a four
+    // and an open three at once
+    let g = game(&[(7, 3, 1), (7, 4, 1), (7, 5, 1), (5, 6, 1), (6, 6, 1), (7, 2, 2), (0, 0, 2), (0, 14, 2), (14, 0, 2), (14, 14, 2)], BLACK);
+    let weights = EvalConfig::default();
+    assert!(g.score_move(7, 6, &weights) - g.score_move(8, 7, &weights) > FOUR_THREE_BONUS);
+
+    let mut fork = g.clone();
+    fork.make_move(7, 6);
+    assert_eq!(fork.threat_count(BLACK), (2, 1));
+    assert!(fork.player_score(BLACK) >= fork.line_scores[0] + FOUR_THREE_BONUS);
+
+    // Without the threat searches, so the fork has to come from eval
+    let mut ai = AI::with_config(15, 15, AIConfig { vcf: false, vct: false, ..Default::default() });
+    assert_eq!(ai.get_ordered_moves_phase1(&g, 0, None)[0], (7, 6));
+    assert_eq!(ai.find_move(&g, 2), Some((7, 6)));
+}
+
+#[test]
+fn center_bias_pulls_quiet_moves_inward() {
+    let g = game(&[(7, 7, 2), (0, 0, 2), (10, 10, 1)], BLACK);
+    let off = EvalConfig { center_weight: 0, ..Default::default() };
+    let on = EvalConfig::default();
+
+    // A lone stone four rings in or one ring in: the same lines, but with
+    // the bias the central one is better for Black, so worse for White
+    let (mut central, mut edge) = (g.clone(), g.clone());
+    central.make_move(4, 4);
+    edge.make_move(1, 4);
+    assert_eq!(central.player_score(BLACK), edge.player_score(BLACK));
+    assert_eq!(central.evaluate(&off), edge.evaluate(&off));
+    assert!(central.evaluate(&on) < edge.evaluate(&on));
+
+    // The reply to a stone near the corner goes toward the middle
+    let g = game(&[(2, 2, 1)], WHITE);
+    let ring = |(row, col): (usize, usize)| row.min(col).min(14 - row).min(14 - col);
+    for depth in [1, 2] {
+        let reply = |eval| AI::with_config(15, 15, AIConfig { eval, ..Default::default() }).find_move(&g, depth).unwrap();
+        assert!(ring(reply(on)) > ring(reply(off)), "depth {depth}");
+    }
+
+    // The bias shrinks as threats appear. Black's stones are 11 and 18
+    // rings in against White's none, and the three halves it.
+    let quiet = game(&[(7, 4, 1), (7, 7, 1), (0, 0, 2), (0, 2, 2)], WHITE);
+    let three = game(&[(7, 5, 1), (7, 6, 1), (7, 7, 1), (0, 0, 2), (0, 2, 2), (0, 4, 2)], WHITE);
+    let bias = |g: &Game| g.evaluate(&off) - g.evaluate(&on);
+    assert_eq!(bias(&quiet), on.center_weight * 11);
+    assert_eq!(bias(&three), on.center_weight * 18 / 2);
+}
+
+#[test]
+fn handicap_stones_start_the_game_for_white() {
+    let g = Game::with_handicap(15, &[(7, 7), (7, 9), (9, 7)]).unwrap();
+    assert_eq!(g.current_player(), WHITE);
+    assert!(g.moves().is_empty());
+    // Scored and hashed like the same board set up by hand, Black ahead
+    let by_hand = game(&[(7, 7, 1), (7, 9, 1), (9, 7, 1)], WHITE);
+    let eval = EvalConfig::default();
+    assert_eq!(g.evaluate(&eval), by_hand.evaluate(&eval));
+    assert_eq!(g.zobrist.get_hash(), by_hand.zobrist.get_hash());
+    assert!(g.evaluate(&eval) < 0);
+    assert_eq!(Game::with_handicap(15, &[]).unwrap().current_player(), BLACK);
+
+    assert_eq!(Game::with_handicap(15, &[(7, 7), (15, 0)]).err(), Some(BoardError::OffBoard { row: 15, col: 0 }));
+    assert_eq!(Game::with_handicap(15, &[(7, 7), (7, 7)]).err(), Some(BoardError::Overlap { row: 7, col: 7 }));
+    let five = [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)];
+    assert_eq!(Game::with_handicap(15, &five).err(), Some(BoardError::AlreadyWon { winner: BLACK }));
+
+    // Four handicap stones in a row are an open four White can't hold
+    let mut g = Game::with_handicap(15, &[(7, 5), (7, 6), (7, 7), (7, 8)]).unwrap();
+    let (row, col) = AI::new(15, 15).find_move(&g, 2).unwrap();
+    assert!([(7, 4), (7, 9)].contains(&(row, col)));
+    g.play(row, col).unwrap();
+    let (row, col) = AI::new(15, 15).find_move(&g, 2).unwrap();
+    g.play(row, col).unwrap();
+    assert_eq!(g.winner(), Some(BLACK));
+}
+
+#[test]
+fn longest_defense_delays_a_forced_loss() {
+    // Black's two open threes can't both be stopped. White's closed three
+    // on row 12 makes a four to hold out with, which the root candidates
+    // are too few to include.
+    let g = game(
+        &[(7, 5, 1), (7, 6, 1), (7, 7, 1), (4, 10, 1), (5, 10, 1), (6, 10, 1), (12, 1, 1), (12, 2, 2), (12, 3, 2), (12, 4, 2), (0, 0, 2), (0, 14, 2), (14, 14, 2)],
+        WHITE,
+    );
+    let narrow = AIConfig { max_candidates: 5, min_candidates: 5, resign_threshold: None, ..Default::default() };
+    let longest = AIConfig { longest_defense: true, ..narrow };
+    let (_, plain) = AI::with_config(15, 15, narrow).find_move_scored(&g, 4).unwrap();
+    let (mv, delayed) = AI::with_config(15, 15, longest).find_move_scored(&g, 4).unwrap();
+    assert!(is_losing_score(plain));
+    assert!([(12, 5), (12, 6)].contains(&mv));
+    assert!(delayed > plain, "{delayed} against {plain}");
+
+    // A timed search doesn't rescore past its deadline
+    let start = Instant::now();
+    assert!(AI::with_config(15, 15, longest).find_move_timed(&g, 50).is_some());
+    assert!(start.elapsed() < Duration::from_millis(250), "{:?}", start.elapsed());
+
+    // Not lost, nothing changes
+    let quiet = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1)], WHITE);
+    let config = AIConfig { longest_defense: true, ..Default::default() };
+    assert_eq!(AI::with_config(15, 15, config).find_move_scored(&quiet, 3), AI::new(15, 15).find_move_scored(&quiet, 3));
+}
+
+#[test]
+fn cells_convert_to_and_from_i8() {
+    for (cell, value) in [(Cell::Empty, EMPTY), (Cell::Black, BLACK), (Cell::White, WHITE)] {
+        assert_eq!(i8::from(cell), value);
+        assert_eq!(Cell::try_from(value), Ok(cell));
+        assert_eq!(cell.opponent().opponent(), cell);
+    }
+    assert_eq!(Cell::Black.opponent(), Cell::White);
+    assert_eq!(Cell::Empty.opponent(), Cell::Empty);
+    assert_eq!(Cell::try_from(3), Err(3));
+    assert_eq!(Cell::try_from(-1), Err(-1));
+
+    // The API's Cells line up with the board's values
+    let g = game(&[(7, 7, 1), (7, 8, 2)], BLACK);
+    assert_eq!(g.to_move(), Cell::Black);
+    assert_eq!(i8::from(g.cell(7, 8)), g.board()[7][8]);
+    assert_eq!(g.is_winning_move(0, 0, Cell::Empty), Ok(false));
+    assert!(g.open_threes(Cell::Empty).is_empty());
+    assert_eq!(g.threat_profile(Cell::Empty), ThreatProfile::default());
+}
+
+#[test]
+fn stop_flag_from_another_thread_ends_the_search() {
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2)], BLACK);
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut ai = AI::new(15, 15);
+    ai.set_stop_flag(Arc::clone(&stop));
+    let setter = {
+        let stop = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            stop.store(true, Ordering::Relaxed);
+        })
+    };
+
+    let start = Instant::now();
+    let result = ai.find_move_scored(&g, MAX_DEPTH);
+    let took = start.elapsed();
+    setter.join().unwrap();
+    assert!(took < Duration::from_millis(400), "{took:?}");
+
+    // The deepest finished iteration's move, as a search to that depth finds
+    let depth = ai.stats.max_depth;
+    assert!((1..MAX_DEPTH).contains(&depth));
+    let (row, col) = result.unwrap().0;
+    assert_eq!(g.board()[row][col], EMPTY);
+    assert_eq!(Some((row, col)), AI::new(15, 15).find_move(&g, depth));
+}
+
+#[test]
+fn beginner_takes_a_five_and_blocks_one() {
+    // Black's four on row 7, closed at (7, 2): Black to move wins at
+    // (7, 7), White to move has to block there
+    let four = [(7, 3, 1), (7, 4, 1), (7, 5, 1), (7, 6, 1), (7, 2, 2), (0, 0, 2), (9, 9, 2)];
+    let win = game(&[&four[..], &[(0, 2, 2)]].concat(), BLACK);
+    let block = game(&four, WHITE);
+    let depth = Difficulty::Beginner.depth();
+    for seed in 0..20 {
+        // However often it blunders, and without the search's own backstop
+        for blunder_percent in [20, 100] {
+            let config = AIConfig { seed: Some(seed), blunder_percent, must_block: false, ..Difficulty::Beginner.config() };
+            let mut ai = AI::with_config(15, 15, config);
+            assert_eq!(ai.find_move_checked(&win, depth).map(|(mv, _)| mv), Some((7, 7)));
+            assert_eq!(ai.find_move_checked(&block, depth).map(|(mv, _)| mv), Some((7, 7)));
+        }
+    }
+
+    // A quiet position is left to the search
+    let quiet = game(&[(7, 7, 1), (7, 8, 2)], BLACK);
+    let config = AIConfig { seed: Some(3), ..Difficulty::Beginner.config() };
+    assert_eq!(AI::with_config(15, 15, config).find_move_checked(&quiet, depth), AI::with_config(15, 15, config).find_move_scored(&quiet, depth));
+}
+
+#[test]
+fn no_cell_near_a_stone_falls_back_to_every_empty_cell() {
+    // At radius 0 no cell is near the lone stone
+    let g = game(&[(7, 7, 1)], WHITE);
+    assert_eq!(g.candidate_cells(1).len(), 8);
+    let fallback = g.candidate_cells(0);
+    assert_eq!(fallback.len(), 15 * 15 - 1);
+    assert!(!fallback.contains(&(7, 7)));
+
+    let mut ai = AI::with_config(15, 15, AIConfig { neighbor_radius: 0, ..Default::default() });
+    let moves = ai.get_ordered_moves_phase1(&g, 0, None);
+    assert!(!moves.is_empty() && moves.iter().all(|&(row, col)| g.board()[row][col] == EMPTY));
+    let ((row, col), _) = ai.find_move_scored(&g, 2).unwrap();
+    assert_eq!(g.board()[row][col], EMPTY);
+    // Below the root there are moves too, not a draw everywhere
+    assert_ne!(ai.negamax(&mut g.clone(), 2, -INF, INF, 0, (7, 7)), 0);
+}
+
+#[test]
+fn progressive_widening_keeps_the_flat_search_move() {
+    // Quiet middlegames from random central stones
+    let mut state = 99;
+    let (mut flat_nodes, mut wide_nodes, mut positions, mut same) = (0, 0, 0, 0);
+    while positions < 8 {
+        let mut g = game(&[], BLACK);
+        for _ in 0..10 {
+            let empty: Vec<_> = (4..11).flat_map(|row| (4..11).map(move |col| (row, col))).filter(|&(row, col)| g.board()[row][col] == EMPTY).collect();
+            let (row, col) = empty[xorshift(&mut state) as usize % empty.len()];
+            g.make_move(row, col);
+        }
+        if g.five_on_board().is_some() || g.threats.iter().any(|&threats| threats > 0) {
+            continue;
+        }
+
+        let config = AIConfig { vcf: false, vct: false, ..Default::default() };
+        let mut flat = AI::with_config(15, 15, config);
+        let mut wide = AI::with_config(15, 15, AIConfig { progressive_widening: true, ..config });
+        let flat_move = flat.find_move(&g, 3);
+        let wide_move = wide.find_move(&g, 3);
+        flat_nodes += flat.stats.nodes;
+        wide_nodes += wide.stats.nodes;
+        positions += 1;
+        same += usize::from(flat_move == wide_move);
+    }
+    assert!(wide_nodes < flat_nodes, "{wide_nodes} nodes widening, {flat_nodes} flat");
+    // The narrower nodes can miss a move now and then, not as a rule
+    assert!(same + 1 >= positions, "{same} of {positions} moves kept");
+}
+
+#[test]
+fn broken_three_outscores_a_two() {
+    // _XX_X_ on row 7 against _XX__ with the third stone off the line
+    let broken = game(&with_corners(&[(7, 5, 1), (7, 6, 1), (7, 8, 1)], 2), WHITE);
+    let two = game(&with_corners(&[(7, 5, 1), (7, 6, 1), (3, 3, 1)], 2), WHITE);
+    assert_eq!(broken.eval_line(7, 5, 0, 1, BLACK).kind, ThreatKind::BrokenThree);
+    assert_eq!(broken.eval_line_static(broken.board(), 7, 5, 0, 1, BLACK).kind, ThreatKind::BrokenThree);
+    assert_eq!(two.eval_line(7, 5, 0, 1, BLACK).kind, ThreatKind::OpenTwo);
+    assert!(broken.evaluate_player(BLACK) > two.evaluate_player(BLACK));
+
+    // Scanned the same way without the bitboard
+    let mut plain = broken.clone();
+    plain.set_bitboard(false);
+    assert_eq!(plain.eval_line(7, 5, 0, 1, BLACK).kind, ThreatKind::BrokenThree);
+    assert_eq!(plain.evaluate_player(BLACK), broken.evaluate_player(BLACK));
+
+    // And as a move, making the broken three beats a developing move
+    let g = game(&with_corners(&[(7, 5, 1), (7, 6, 1)], 2), BLACK);
+    let weights = EvalConfig::default();
+    assert!(g.score_move(7, 8, &weights) > g.score_move(10, 10, &weights));
+}
+
+#[test]
+fn winning_move_completes_the_open_four_only() {
+    let g = game(&[(7, 4, 1), (7, 5, 1), (7, 6, 1), (7, 7, 1), (0, 0, 2), (0, 1, 2), (0, 2, 2), (14, 14, 2)], BLACK);
+    // Either end of the open four makes five, for Black only
+    assert_eq!(g.is_winning_move(7, 8, Cell::Black), Ok(true));
+    assert_eq!(g.is_winning_move(7, 3, Cell::Black), Ok(true));
+    assert_eq!(g.is_winning_move(7, 8, Cell::White), Ok(false));
+    // A developing move next to it doesn't
+    assert_eq!(g.is_winning_move(8, 8, Cell::Black), Ok(false));
+    assert_eq!(g.is_winning_move(6, 5, Cell::Black), Ok(false));
+
+    assert_eq!(g.is_winning_move(7, 7, Cell::Black), Err(MoveError::Occupied));
+    assert_eq!(g.is_winning_move(15, 0, Cell::Black), Err(MoveError::OutOfBounds));
+    assert_eq!(g.is_winning_move(0, 15, Cell::Black), Err(MoveError::OutOfBounds));
+}
+
+#[test]
+fn single_four_is_blocked_and_double_four_is_lost() {
+    // White's four on row 7, closed by Black at (7, 3), Black to move
+    let corners = [(0, 0, 1), (0, 14, 1), (14, 0, 1), (14, 14, 1)];
+    let four = [(7, 3, 1), (7, 4, 2), (7, 5, 2), (7, 6, 2), (7, 7, 2)];
+    let g = game(&[&corners[..], &four].concat(), BLACK);
+    assert_eq!(g.defense(), Defense::Four((7, 8)));
+    let (mv, score) = AI::new(15, 15).find_move_scored(&g, 4).unwrap();
+    assert_eq!(mv, (7, 8));
+    assert!(!is_losing_score(score));
+
+    // A second four down column 10: one block, and the other one wins
+    let column = [(3, 10, 1), (4, 10, 2), (5, 10, 2), (6, 10, 2), (8, 10, 2)];
+    let g = game(&[&corners[..], &four, &column].concat(), BLACK);
+    assert!(matches!(g.defense(), Defense::DoubleFour(_)));
+    assert_eq!(AI::new(15, 15).find_move_scored(&g, 4).map(|(_, score)| score), Some(-(WIN - 1)));
+
+    // The four and an open three down column 11: the block holds for a move
+    let three = [(4, 11, 2), (5, 11, 2), (6, 11, 2), (1, 1, 1), (1, 2, 1), (2, 1, 1)];
+    let g = game(&[&corners[..], &four, &three].concat(), BLACK);
+    assert_eq!(g.defense(), Defense::FourThree((7, 8)));
+    assert_eq!(AI::new(15, 15).find_move_scored(&g, 4), Some(((7, 8), -(WIN - 3))));
+
+    assert_eq!(game(&corners, BLACK).defense(), Defense::Free);
+}
+
+#[test]
+fn suggested_depth_never_grows_with_the_board() {
+    for budget in [0, 50, 200, 1000, 5000, 60_000] {
+        let depths: Vec<_> = [7, 9, 13, 15, 19, 25].iter().map(|&size| AI::suggest_depth(size, budget)).collect();
+        assert!(depths.windows(2).all(|w| w[0] >= w[1]), "{budget}ms: {depths:?}");
+        assert!(depths.iter().all(|&d| (1..=MAX_DEPTH).contains(&d)));
+    }
+    // More time, deeper; no time, depth 1; no cells, the cap
+    assert!(AI::suggest_depth(15, 5000) > AI::suggest_depth(15, 100));
+    assert_eq!(AI::suggest_depth(15, 0), 1);
+    assert_eq!(AI::suggest_depth(0, u64::MAX), MAX_DEPTH);
+}
+
+#[test]
+fn root_moves_keep_one_move_per_symmetry_class() {
+    let ai = AI::new(15, 15);
+
+    // Empty board: all eight symmetries hold, and the one move is the
+    // center, which each of them maps onto itself
+    let g = game(&[], BLACK);
+    assert_eq!(g.symmetries().len(), 8);
+    assert_eq!(ai.get_ordered_moves_phase1(&g, 0, None), vec![(7, 7)]);
+    assert!(g.symmetries().iter().all(|&sym| g.zobrist.transform(sym, (7, 7)) == (7, 7)));
+
+    // One center stone: the candidates around it fall into five classes,
+    // and each candidate is the image of exactly one root move
+    let g = game(&[(7, 7, 1)], WHITE);
+    let root = ai.get_ordered_moves_phase1(&g, 0, None);
+    let mut classes: Vec<_> = root
+        .iter()
+        .map(|&(r, c)| (r.abs_diff(7).min(c.abs_diff(7)), r.abs_diff(7).max(c.abs_diff(7))))
+        .collect();
+    classes.sort();
+    assert_eq!(classes, vec![(0, 1), (0, 2), (1, 1), (1, 2), (2, 2)]);
+    for cell in g.candidate_cells(ai.config.neighbor_radius) {
+        let images = root.iter().filter(|&&mv| g.symmetries().iter().any(|&sym| g.zobrist.transform(sym, mv) == cell));
+        assert_eq!(images.count(), 1, "{cell:?}");
+    }
+
+    // Below the root only the candidate limit applies
+    let below = ai.get_ordered_moves_phase1(&g, 1, None).len();
+    assert_eq!(below, ai.candidate_limit(1).min(g.candidate_cells(ai.config.neighbor_radius).len()));
+    assert!(below > root.len());
+}
+
+#[test]
+fn each_error_serializes_to_its_tagged_shape() {
+    use serde_json::json;
+    let shape = |e: GomokuError| serde_json::to_value(&e).unwrap();
+
+    assert_eq!(shape(GomokuError::InvalidPosition { row: 3, col: 15 }), json!({"kind": "InvalidPosition", "row": 3, "col": 15}));
+    assert_eq!(shape(GomokuError::InvalidBoard { reason: BoardError::Ragged }), json!({"kind": "InvalidBoard", "reason": "Ragged"}));
+    assert_eq!(
+        shape(BoardError::InvalidCell { row: 1, col: 2, value: 7 }.into()),
+        json!({"kind": "InvalidBoard", "reason": {"InvalidCell": {"row": 1, "col": 2, "value": 7}}})
+    );
+    assert_eq!(shape(GomokuError::GameOver { winner: BLACK }), json!({"kind": "GameOver", "winner": 1}));
+    assert_eq!(shape(GomokuError::IllegalMove { reason: MoveError::Occupied }), json!({"kind": "IllegalMove", "reason": "Occupied"}));
+    assert_eq!(shape(GomokuError::NoMove), json!({"kind": "NoMove"}));
+    assert_eq!(shape(GomokuError::NoPuzzle), json!({"kind": "NoPuzzle"}));
+    assert_eq!(shape(GomokuError::InvalidSize), json!({"kind": "InvalidSize"}));
+    assert_eq!(
+        shape(GomokuError::InvalidWinLength { win_length: 2, max: 15 }),
+        json!({"kind": "InvalidWinLength", "win_length": 2, "max": 15})
+    );
+    assert_eq!(shape(GomokuError::UnknownSession), json!({"kind": "UnknownSession"}));
+    assert_eq!(shape(GomokuError::GameChanged), json!({"kind": "GameChanged"}));
+    assert_eq!(shape(GomokuError::NotSwap2Decision), json!({"kind": "NotSwap2Decision"}));
+    assert_eq!(shape(GomokuError::InvalidSave { message: "bad".into() }), json!({"kind": "InvalidSave", "message": "bad"}));
+    assert_eq!(shape(GomokuError::Internal { message: "poisoned".into() }), json!({"kind": "Internal", "message": "poisoned"}));
+
+    // And the frontend's copy reads back
+    let back: GomokuError = serde_json::from_value(json!({"kind": "InvalidWinLength", "win_length": 9, "max": 7})).unwrap();
+    assert_eq!(back, GomokuError::InvalidWinLength { win_length: 9, max: 7 });
+}
+
+#[test]
+fn history_decays_across_successive_searches() {
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2)], BLACK);
+    let total = |ai: &AI| ai.history.iter().flatten().flatten().map(|&h| h as i64).sum::<i64>();
+
+    let mut ai = AI::new(15, 15);
+    ai.find_move(&g, 4);
+    let before = ai.history.clone();
+    assert!(total(&ai) > 0);
+    assert!(ai.killer_moves.iter().any(|killers| killers[0].is_some()));
+
+    // A depth 0 search adds nothing, so all it leaves is the aging: no
+    // killers and every history entry halved
+    ai.find_move(&g, 0);
+    assert!(ai.killer_moves.iter().all(|killers| killers == &[None, None]));
+    for (aged, old) in ai.history.iter().flatten().flatten().zip(before.iter().flatten().flatten()) {
+        assert_eq!(*aged, old / 2);
+    }
+
+    // Searching the same position again and again, the halving keeps the
+    // history within twice what one fresh search builds
+    let mut fresh = AI::new(15, 15);
+    fresh.find_move(&g, 4);
+    for _ in 0..4 {
+        ai.tt.clear();
+        ai.find_move(&g, 4);
+        assert!(total(&ai) <= 2 * total(&fresh), "{} > 2 * {}", total(&ai), total(&fresh));
+    }
+}
+
+#[test]
+fn critical_squares_list_one_win_for_each_side() {
+    // Black's four on row 3, closed at (3, 2), and White's down column 10,
+    // closed at (4, 10)
+    let stones = [
+        (3, 3, 1), (3, 4, 1), (3, 5, 1), (3, 6, 1), (4, 10, 1), (14, 14, 1),
+        (3, 2, 2), (5, 10, 2), (6, 10, 2), (7, 10, 2), (8, 10, 2), (0, 0, 2),
+    ];
+    let black = game(&stones, BLACK);
+    assert_eq!(black.critical_squares(), CriticalSquares { wins: vec![(3, 7)], losses: vec![(9, 10)] });
+
+    // With White to move the two lists swap
+    let white = game(&[&stones[..], &[(14, 7, 1)]].concat(), WHITE);
+    assert_eq!(white.critical_squares(), CriticalSquares { wins: vec![(9, 10)], losses: vec![(3, 7)] });
+
+    assert_eq!(game(&[(7, 7, 1)], WHITE).critical_squares(), CriticalSquares { wins: vec![], losses: vec![] });
+}
+
+#[test]
+fn extensions_find_a_win_one_ply_past_the_depth() {
+    // Black's (7, 7) makes a three on row 7, a forcing move, and wins one
+    // ply past what depth 3 reads
+    let stones = [
+        (7, 5, 1), (7, 6, 1), (6, 7, 1), (5, 9, 1), (6, 9, 1),
+        (7, 9, 2), (0, 0, 2), (0, 14, 2), (14, 0, 2), (14, 14, 2),
+    ];
+    let g = game(&stones, BLACK);
+    let config = AIConfig { vcf: false, vct: false, ..Default::default() };
+
+    let (_, plain) = AI::with_config(15, 15, config).find_move_scored(&g, 3).unwrap();
+    assert!(!is_winning_score(plain));
+    let (_, deeper) = AI::with_config(15, 15, config).find_move_scored(&g, 4).unwrap();
+    assert!(is_winning_score(deeper));
+
+    // One extension for the forcing first move is the missing ply, and
+    // the counter is back at 0 afterwards
+    let mut ai = AI::with_config(15, 15, AIConfig { max_extensions: 1, ..config });
+    let (mv, extended) = ai.find_move_scored(&g, 3).unwrap();
+    assert_eq!(mv, (7, 7));
+    assert!(is_winning_score(extended));
+    assert_eq!(ai.extensions, 0);
+}
+
+#[test]
+fn puzzle_solution_is_a_forced_win() {
+    for (size, win_in, seed) in [(15, 2, 1), (15, 3, 2), (11, 3, 3)] {
+        let mut ai = AI::with_config(size, size, AIConfig { seed: Some(seed), ..Default::default() });
+        let (puzzle, solution) = ai.generate_puzzle(size, win_in).unwrap();
+        assert_eq!(puzzle.board[solution.0][solution.1], EMPTY);
+        assert!(puzzle.winner().is_none());
+
+        // Continuous fours from the solution win in time, and after it the
+        // defender's search finds every reply lost
+        let line = ai.vcf_search(&puzzle, win_in).unwrap();
+        assert_eq!(line[0], solution);
+        assert!(line.len().div_ceil(2) <= win_in);
+        let mut played = puzzle.clone();
+        played.play(solution.0, solution.1).unwrap();
+        let (_, score) = AI::new(size, size).find_move_scored(&played, 2 * win_in - 1).unwrap();
+        assert!(is_losing_score(score), "seed {seed}: {score}");
+
+        // The same seed gives the same puzzle
+        let mut again = AI::with_config(size, size, AIConfig { seed: Some(seed), ..Default::default() });
+        let (same, same_solution) = again.generate_puzzle(size, win_in).unwrap();
+        assert_eq!((same.board, same_solution), (puzzle.board, solution));
+    }
+    assert!(AI::new(15, 15).generate_puzzle(15, 1).is_none());
+}
+
+#[test]
+fn explore_move_matches_playing_the_move_by_hand() {
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2)], BLACK);
+    let explored = AI::new(15, 15).explore_move(&g, (9, 9), 3).unwrap();
+    assert_eq!(g.stone_count(), 4);
+
+    let mut by_hand = g.clone();
+    by_hand.play(9, 9).unwrap();
+    let (reply, score) = AI::new(15, 15).find_move_scored(&by_hand, 3).unwrap();
+    by_hand.play(reply.0, reply.1).unwrap();
+    assert_eq!(explored, Exploration { reply: Some(reply), score, eval: by_hand.evaluate(&EvalConfig::default()) });
+
+    // A move that makes five ends it there; occupied and off-board cells
+    // are refused
+    let four = game(&[(7, 4, 1), (7, 5, 1), (7, 6, 1), (7, 7, 1), (0, 0, 2), (0, 2, 2), (0, 4, 2), (14, 14, 2)], BLACK);
+    let mut ai = AI::new(15, 15);
+    assert_eq!(ai.explore_move(&four, (7, 8), 2), Ok(Exploration { reply: None, score: -WIN, eval: WIN }));
+    assert_eq!(ai.explore_move(&four, (7, 4), 2), Err(MoveError::Occupied));
+    assert_eq!(ai.explore_move(&four, (15, 4), 2), Err(MoveError::OutOfBounds));
+}
+
+#[test]
+fn depth_preferred_keeps_a_deep_entry_through_a_shallow_store() {
+    let g = game(&[(7, 7, 1), (7, 8, 2)], BLACK);
+    let entry = |depth, flag, score| TTEntry { depth, score, flag, best_move: Some((6, 6)) };
+
+    let mut ai = AI::new(15, 15);
+    ai.tt_put(&g, entry(6, TTFlag::LowerBound, 100));
+    ai.tt_put(&g, entry(2, TTFlag::LowerBound, 5));
+    ai.tt_put(&g, entry(2, TTFlag::UpperBound, 5));
+    assert_eq!(ai.tt_get(&g).map(|e| (e.depth, e.score)), Some((6, 100)));
+
+    // An exact score replaces a deeper bound, but not a deeper exact one;
+    // a store as deep replaces anything
+    ai.tt_put(&g, entry(3, TTFlag::Exact, 7));
+    assert_eq!(ai.tt_get(&g).map(|e| (e.depth, e.score)), Some((3, 7)));
+    ai.tt_put(&g, entry(2, TTFlag::Exact, 8));
+    assert_eq!(ai.tt_get(&g).map(|e| e.score), Some(7));
+    ai.tt_put(&g, entry(3, TTFlag::UpperBound, 9));
+    assert_eq!(ai.tt_get(&g).map(|e| e.score), Some(9));
+
+    // Always lets the shallow store through
+    let mut always = AI::with_config(15, 15, AIConfig { tt_replacement: TTReplacement::Always, ..Default::default() });
+    always.tt_put(&g, entry(6, TTFlag::Exact, 100));
+    always.tt_put(&g, entry(1, TTFlag::UpperBound, 5));
+    assert_eq!(always.tt_get(&g).map(|e| e.depth), Some(1));
+
+    // A shallow search after a deep one leaves the root's entry as it was
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2)], BLACK);
+    let mut ai = AI::new(15, 15);
+    ai.find_move(&g, 5);
+    let deep = ai.tt_get(&g).unwrap().depth;
+    ai.find_move(&g, 2);
+    assert_eq!(ai.tt_get(&g).unwrap().depth, deep);
+}
+
+#[test]
+fn mirroring_opponent_is_met_with_the_symmetry_breaking_move() {
+    // White opened at (6, 7) and has copied Black's moves through the
+    // center since. (8, 7) is the cell opposite White's first stone, next
+    // to Black's center stone.
+    let mirrored = [(7, 7, 1), (6, 7, 2), (5, 5, 1), (9, 9, 2), (6, 9, 1), (8, 5, 2)];
+    let g = game(&mirrored, BLACK);
+    let mut ai = AI::new(15, 15);
+    assert_eq!(ai.mirror_break(&g), Some((8, 7)));
+    assert_eq!(ai.find_move(&g, 4), Some((8, 7)));
+
+    // Not once a copy is missing, nor with the heuristic off
+    let broken = [(7, 7, 1), (6, 7, 2), (5, 5, 1), (9, 8, 2), (6, 9, 1), (8, 5, 2)];
+    assert_eq!(ai.mirror_break(&game(&broken, BLACK)), None);
+    let off = AI::with_config(15, 15, AIConfig { anti_mirror: false, ..Default::default() });
+    assert_eq!(off.mirror_break(&g), None);
+}
+
+#[test]
+fn each_threat_kind_has_its_shape() {
+    use ThreatKind::*;
+    // Row 7 written out, x for Black's stones and o for White's. The line
+    // through Black's first stone, the same with or without bitboards.
+    let kind = |row: &str, win_length: usize, allow_overline: bool| {
+        let stones: Vec<_> = row
+            .chars()
+            .enumerate()
+            .filter_map(|(col, ch)| match ch {
+                'x' => Some((7, col, BLACK)),
+                'o' => Some((7, col, WHITE)),
+                _ => None,
+            })
+            .collect();
+        let mut g = Game::from_board(board(15, &stones), WHITE).unwrap();
+        g.set_win_length(win_length);
+        g.set_allow_overline(allow_overline);
+        let first = row.find('x').unwrap();
+        let line = g.eval_line(7, first, 0, 1, BLACK);
+        g.bits = None;
+        assert_eq!(g.eval_line(7, first, 0, 1, BLACK), line, "{row}");
+        line.kind
+    };
+
+    let shapes = [
+        ("_xxxxx_", Five),
+        ("_xxxx_", OpenFour),
+        ("oxxxx_", ClosedFour),
+        ("oxx_xxo", ClosedFour),
+        ("oxxxxo", DeadFour),
+        ("_xxx_", OpenThree),
+        ("_xx_x_", BrokenThree),
+        ("oxxx_", ClosedThree),
+        ("oxx_x_", ClosedThree),
+        ("_xx_", OpenTwo),
+        ("_x_x_", SplitTwo),
+        ("oxx_", ClosedTwo),
+        ("oxxxo", Other),
+    ];
+    for (row, want) in shapes {
+        assert_eq!(kind(row, 5, true), want, "{row}");
+    }
+    let all = [Other, DeadFour, ClosedTwo, SplitTwo, OpenTwo, ClosedThree, BrokenThree, OpenThree, ClosedFour, OpenFour, Five];
+    assert!(all.iter().all(|want| shapes.iter().any(|(_, kind)| kind == want)));
+
+    // Overlines and other win lengths
+    assert_eq!(kind("_xxxxxx_", 5, true), Five);
+    assert_eq!(kind("_xxxxxx_", 5, false), Other);
+    assert_eq!(kind("_xxxx_", 4, true), Five);
+    assert_eq!(kind("_xx_", 4, true), OpenThree);
+}
+
+#[test]
+fn depth_two_trace_has_a_node_per_root_move() {
+    let g = game(&[(7, 7, 1), (7, 8, 2), (8, 8, 1)], WHITE);
+    let mut roots = AI::new(15, 15).get_ordered_moves_phase1(&g, 0, None);
+    let mut ai = AI::with_config(15, 15, AIConfig { trace_depth: 2, ..Default::default() });
+    let best = ai.find_move(&g, 2).unwrap();
+
+    // The root's children are the moves it generated, each with the
+    // replies under it and nothing below those
+    let trace = ai.trace();
+    let mut traced: Vec<_> = trace.iter().map(|node| node.mv).collect();
+    traced.sort();
+    roots.sort();
+    assert_eq!(traced, roots);
+    assert!(trace.iter().all(|node| !node.children.is_empty() && node.children.iter().all(|reply| reply.children.is_empty())));
+    assert!(trace.iter().flat_map(|node| &node.children).any(|reply| reply.cutoff));
+    let top = trace.iter().map(|node| node.score).max();
+    assert_eq!(trace.iter().find(|node| node.mv == best).map(|node| node.score), top);
+
+    // Nothing is traced by default
+    let mut ai = AI::new(15, 15);
+    ai.find_move(&g, 2);
+    assert!(ai.trace().is_empty());
+}
+
+#[test]
+fn torus_wins_across_the_edge_where_a_plain_board_does_not() {
+    // Black's row runs off the right edge and back in on the left
+    let stones = [(7, 13, 1), (7, 14, 1), (7, 0, 1), (7, 1, 1), (3, 3, 2), (4, 4, 2), (5, 9, 2), (10, 10, 2)];
+    for wrap in [false, true] {
+        let mut g = game(&stones, BLACK);
+        g.set_wrap(wrap);
+        assert_eq!((g.wins_at(7, 2, BLACK), g.wins_at(7, 12, BLACK)), (wrap, wrap), "wrap {wrap}");
+        let mut played = g.clone();
+        played.play(7, 2).unwrap();
+        assert_eq!(played.winner(), wrap.then_some(BLACK));
+        if wrap {
+            assert_eq!(played.winning_line(), Some(vec![(7, 13), (7, 14), (7, 0), (7, 1), (7, 2)]));
+            assert!(matches!(AI::new(15, 15).find_move(&g, 2), Some((7, 2) | (7, 12))));
+        }
+    }
+
+    // Bitboards are off while the board wraps, and back after only if
+    // they were on before
+    let mut g = game(&stones, BLACK);
+    g.set_wrap(true);
+    assert!(g.bits.is_none());
+    g.set_wrap(false);
+    assert!(g.bits.is_some());
+    g.set_bitboard(false);
+    g.set_wrap(true);
+    g.set_wrap(false);
+    assert!(g.bits.is_none());
+
+    // Renju names the cells of a three that crosses the edge as they are
+    // on the board
+    let threes = [(7, 13, 1), (7, 14, 1), (6, 0, 1), (8, 0, 1), (3, 7, 2), (3, 9, 2), (11, 7, 2), (11, 9, 2)];
+    let mut g = renju_game(&threes);
+    g.set_wrap(true);
+    assert_eq!(
+        g.forbidden_reason(7, 0),
+        Some(Forbidden::DoubleThree { threes: vec![vec![(7, 13), (7, 14), (7, 0)], vec![(6, 0), (7, 0), (8, 0)]] })
+    );
+}
+
+#[test]
+fn stability_counts_only_the_depths_since_the_best_move_last_changed() {
+    // The best move changes between iterations, and only the deepest one
+    // backs the move played
+    let flips = [(7, 7, 1), (7, 6, 2), (7, 8, 1), (7, 5, 2), (7, 4, 1), (8, 4, 2), (7, 9, 1), (9, 4, 2)];
+    let mut ai = AI::new(15, 15);
+    let mv = ai.find_move(&game(&flips, BLACK), 4).unwrap();
+    assert_eq!(ai.iteration_moves.len(), 4);
+    assert_ne!(ai.iteration_moves[0], mv);
+    assert_eq!(ai.stability(mv), 1);
+    assert_eq!(ai.stability(ai.iteration_moves[0]), 0);
+
+    // Settled from depth 2 on
+    let mut ai = AI::new(15, 15);
+    let mv = ai.find_move(&game(&[(7, 7, 1), (6, 6, 2), (7, 8, 1), (6, 7, 2)], BLACK), 4).unwrap();
+    assert_eq!(ai.stability(mv), 3);
+}
+
+#[test]
+fn open_threes_lists_each_three_with_its_extension_cells() {
+    // A row three and a diagonal one for Black; its closed column three
+    // and broken three don't count, and White has a three of its own
+    let stones = [
+        (7, 5, 1), (7, 6, 1), (7, 7, 1),
+        (2, 2, 1), (3, 3, 1), (4, 4, 1),
+        (10, 0, 1), (11, 0, 1), (12, 0, 2), (9, 0, 2),
+        (12, 5, 1), (12, 6, 1), (12, 8, 1),
+        (0, 10, 2), (0, 11, 2), (0, 12, 2),
+    ];
+    let g = game(&stones, WHITE);
+    let threes = g.open_threes(Cell::Black);
+    assert_eq!(threes.len(), 2, "{threes:?}");
+    assert!(threes.contains(&OpenThree { stones: vec![(7, 5), (7, 6), (7, 7)], ends: [(7, 4), (7, 8)] }));
+    assert!(threes.contains(&OpenThree { stones: vec![(2, 2), (3, 3), (4, 4)], ends: [(1, 1), (5, 5)] }));
+    assert_eq!(g.open_threes(Cell::White), vec![OpenThree { stones: vec![(0, 10), (0, 11), (0, 12)], ends: [(0, 9), (0, 13)] }]);
+}
+
+#[test]
+fn neighbor_counts_match_a_recount_through_a_random_game() {
+    // Compared outright rather than left to has_neighbor's debug_assert,
+    // which a release build compiles out
+    for (seed, wrap) in [(21, false), (22, true)] {
+        let mut g = Game::from_board(board(11, &[]), BLACK).unwrap();
+        g.set_wrap(wrap);
+        random_play(&mut g, seed, 120, |g| {
+            let mut fresh = g.clone();
+            fresh.recount_neighbors();
+            assert_eq!(g.neighbors, fresh.neighbors, "wrap {wrap}");
+            for (row, col) in (0..11).flat_map(|r| (0..11).map(move |c| (r, c))) {
+                assert_eq!(g.neighbors[row][col] > 0, g.scan_neighbors(row, col, NEIGHBOR_RADIUS));
+            }
+        });
+    }
+}
+
+#[test]
+fn split_two_scores_between_two_lone_stones_and_an_open_two() {
+    // _X_X_ can still grow into a broken three, _X__X_ is two lone stones
+    let eval = |stones: &[(usize, usize, i8)]| game(&[stones, &[(0, 0, 2), (14, 14, 2)]].concat(), BLACK).evaluate_player(BLACK);
+    let apart = eval(&[(7, 5, 1), (7, 8, 1)]);
+    let split = eval(&[(7, 5, 1), (7, 7, 1)]);
+    let solid = eval(&[(7, 5, 1), (7, 6, 1)]);
+    assert!(apart < split && split < solid, "{apart} {split} {solid}");
+
+    // Move ordering ranks the three moves next to a lone stone the same way
+    let g = game(&[(7, 5, 1), (0, 0, 2)], BLACK);
+    let config = EvalConfig { opponent_weight: 0.0, ..Default::default() };
+    let (apart, split, solid) = (g.score_move(7, 8, &config), g.score_move(7, 7, &config), g.score_move(7, 6, &config));
+    assert!(apart < split && split < solid, "{apart} {split} {solid}");
+}
+
+#[test]
+fn resumed_checkpoint_searches_on_to_the_uninterrupted_result() {
+    let positions = [
+        vec![(7, 7, 1), (7, 8, 2), (8, 8, 1)],
+        vec![(7, 7, 1), (6, 6, 2), (7, 8, 1), (6, 7, 2)],
+    ];
+    for stones in positions {
+        let g = game(&stones, if stones.len() % 2 == 0 { BLACK } else { WHITE });
+        let mut whole = AI::new(15, 15);
+        let want = whole.find_move_scored(&g, 5);
+
+        // Stopped after depth 3, saved as JSON and picked up by a new AI
+        let mut first = AI::new(15, 15);
+        first.find_move_scored(&g, 3);
+        let checkpoint = first.checkpoint();
+        assert_eq!(checkpoint.depth, 3);
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let mut later = AI::new(15, 15);
+        later.resume(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(later.find_move_from(&g, 5, None), want, "{stones:?}");
+        assert_eq!(later.stats.max_depth, 5);
+        assert_eq!(later.iteration_moves, whole.iteration_moves);
+    }
+
+    // Another position starts over from depth 1, another board size is refused
+    let mut ai = AI::new(15, 15);
+    ai.find_move_scored(&game(&[(7, 7, 1)], WHITE), 2);
+    ai.find_move_from(&game(&[(3, 3, 1)], WHITE), 2, None);
+    assert_eq!(ai.iteration_moves.len(), 2);
+    assert!(AI::new(9, 9).resume(ai.checkpoint()).is_err());
+}
+
+#[test]
+fn gapped_five_stone_line_is_dead_where_only_exact_fives_win() {
+    // Black's stones along row 7 from column 3, x for Black and o for White
+    let line = |row: &str, allow_overline: bool| {
+        let stones: Vec<_> = row
+            .chars()
+            .enumerate()
+            .filter_map(|(i, ch)| match ch {
+                'x' => Some((7, i + 3, BLACK)),
+                'o' => Some((7, i + 3, WHITE)),
+                _ => None,
+            })
+            .collect();
+        let mut g = game(&stones, WHITE);
+        g.set_allow_overline(allow_overline);
+        let first = row.find('x').unwrap() + 3;
+        let kind = g.eval_line(7, first, 0, 1, BLACK).kind;
+        (kind, g.winning_cells(BLACK).len())
+    };
+
+    // XX_XX completes to an exact five, XXX_XX only to six
+    assert_eq!(line("oxx_xxo", false), (ThreatKind::ClosedFour, 1));
+    assert_eq!(line("oxxx_xxo", false), (ThreatKind::DeadFour, 0));
+    assert_eq!(line("_xxx_xx_", false), (ThreatKind::DeadFour, 0));
+    // Where overlines win, the gap still makes one
+    assert_eq!(line("_xxx_xx_", true), (ThreatKind::ClosedFour, 1));
+    assert_eq!(line("oxxx_xxo", true), (ThreatKind::ClosedFour, 1));
+}
@@ -0,0 +1,55 @@
+// Torus boards: lines run off one edge and back in at the opposite one, so
+// every cell is as central as any other. Runs are capped at the length of
+// the ring they lie on, so a line can't count its own stones twice.
+
+use super::Game;
+
+impl Game {
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        // Bitboard lanes stop at the edges, so they're dropped while the
+        // board wraps and come back after if they were on before
+        self.set_bitboard(self.bitboard);
+        self.recount_neighbors();
+        self.refresh_eval();
+    }
+
+    pub fn wrap(&self) -> bool {
+        self.wrap
+    }
+
+    // The cell at (r, c), which may be off the board: wrapped back onto it
+    // on a torus, None otherwise
+    pub(super) fn cell_at(&self, r: i32, c: i32) -> Option<(usize, usize)> {
+        wrapped_cell(self.rows, self.cols, self.wrap, r, c)
+    }
+
+    // Cells on a line in direction (dr, dc) before it comes back to where
+    // it started, unlimited off a torus
+    pub(super) fn ring(&self, dr: i32, dc: i32) -> usize {
+        if self.wrap { ring_len(self.rows, self.cols, dr, dc) } else { usize::MAX }
+    }
+}
+
+pub(super) fn wrapped_cell(rows: usize, cols: usize, wrap: bool, r: i32, c: i32) -> Option<(usize, usize)> {
+    let (rows, cols) = (rows as i32, cols as i32);
+    if wrap {
+        Some((r.rem_euclid(rows) as usize, c.rem_euclid(cols) as usize))
+    } else {
+        (r >= 0 && r < rows && c >= 0 && c < cols).then_some((r as usize, c as usize))
+    }
+}
+
+// A row goes round in `cols` steps and a column in `rows`; a diagonal
+// needs a multiple of both
+pub(super) fn ring_len(rows: usize, cols: usize, dr: i32, dc: i32) -> usize {
+    match (dr, dc) {
+        (0, _) => cols,
+        (_, 0) => rows,
+        _ => rows / gcd(rows, cols) * cols,
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
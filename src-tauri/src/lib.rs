@@ -0,0 +1,7 @@
+// Gomoku engine, independent of Tauri. main.rs wraps it in commands; other
+// frontends can use it directly, including wasm32-unknown-unknown with
+// --no-default-features. There, stick to the single-threaded searches:
+// find_move_parallel and pondering spawn threads, and the timed and stats
+// searches read the clock.
+
+pub mod ai_core;
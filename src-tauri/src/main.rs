@@ -6,10 +6,12 @@
     windows_subsystem = "windows"
 )]
 
-mod ai_core;
-
-use ai_core::{AI, Game};
+use gomoku_tauri::ai_core::{self, check_board_shape, is_losing_score, is_winning_score, swap2_phase, AIConfig, BoardError, CriticalSquares, Difficulty, EvalConfig, Exploration, Forbidden, GomokuError, MoveAnalysis, OpenThree, SearchStats, AI, BoardInfo, Game, GameState, Swap2Color, Swap2Option, Swap2Phase, ThreatKind, TraceNode};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::Emitter;
 
 #[derive(Serialize, Deserialize)]
 struct MoveResult {
@@ -17,36 +19,705 @@ struct MoveResult {
     col: usize,
 }
 
+// Chosen move with the engine's opinion of the position
+#[derive(Serialize, Deserialize)]
+struct AiMove {
+    row: usize,
+    col: usize,
+    score: i32,
+    forced_win: bool,
+    forced_loss: bool,
+    // Lost past the AI's resign threshold, the frontend can show a resignation
+    resign: bool,
+    // Deepest iterations in a row that agreed on this move, see AI::stability
+    stability: usize,
+}
+
+impl AiMove {
+    fn new(ai: &AI, (row, col): (usize, usize), score: i32) -> Self {
+        AiMove {
+            row,
+            col,
+            score,
+            forced_win: is_winning_score(score),
+            forced_loss: is_losing_score(score),
+            resign: ai.resigns(score),
+            stability: ai.stability((row, col)),
+        }
+    }
+}
+
+// Swap2 decision: "swap", "stay", "place_two", "take_black" or "take_white",
+// with the stones to play (reply, or black then white for place_two)
+#[derive(Serialize, Deserialize)]
+struct Swap2Decision {
+    action: String,
+    moves: Vec<MoveResult>,
+}
+
+impl Swap2Decision {
+    fn new(action: &str, moves: &[(usize, usize)]) -> Self {
+        Swap2Decision {
+            action: action.to_string(),
+            moves: moves.iter().map(|&(row, col)| MoveResult { row, col }).collect(),
+        }
+    }
+}
+
+// Chosen move with how much searching it took
+#[derive(Serialize, Deserialize)]
+struct MoveStats {
+    row: usize,
+    col: usize,
+    stats: SearchStats,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Threat {
+    row: usize,
+    col: usize,
+    kind: ThreatKind,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoveScore {
+    row: usize,
+    col: usize,
+    score: i32,
+}
+
+// A position to solve: current_player wins by continuous fours starting
+// at `solution`, left out unless asked for
+#[derive(Serialize, Deserialize)]
+struct Puzzle {
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    solution: Option<MoveResult>,
+}
+
+// Static evaluation: `score` for the side to move, plus each player's own total
+#[derive(Serialize, Deserialize)]
+struct Evaluation {
+    score: i32,
+    black: i32,
+    white: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BoardCheck {
+    valid: bool,
+    reason: Option<BoardError>,
+    message: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct GameStatus {
     is_win: bool,
     winner: Option<i8>,
+    is_draw: bool,
+    // Stones of the winning five, for highlighting
+    winning_line: Option<Vec<(usize, usize)>>,
+}
+
+impl GameStatus {
+    fn of(game: &Game) -> Self {
+        let winner = game.winner();
+        GameStatus {
+            is_win: winner.is_some(),
+            winner,
+            is_draw: winner.is_none() && game.is_full(),
+            winning_line: game.winning_line(),
+        }
+    }
+}
+
+// Where a dropped stone landed
+#[derive(Serialize, Deserialize)]
+struct DropResult {
+    row: usize,
+    status: GameStatus,
+}
+
+// Set by cancel_search to stop get_ai_move_streaming early
+#[derive(Default)]
+struct SearchCancel(Arc<AtomicBool>);
+
+type SessionId = u64;
+
+// Games kept on the backend for the session commands, so the frontend
+// doesn't have to send the board and history every call
+#[derive(Default)]
+struct Sessions {
+    games: Mutex<HashMap<SessionId, Game>>,
+    next_id: AtomicU64,
+}
+
+impl Sessions {
+    fn with_game<T>(&self, session: SessionId, f: impl FnOnce(&mut Game) -> Result<T, GomokuError>) -> Result<T, GomokuError> {
+        let mut games = self.games.lock()?;
+        let game = games.get_mut(&session).ok_or(GomokuError::UnknownSession)?;
+        f(game)
+    }
+
+    fn play(&self, session: SessionId, row: usize, col: usize) -> Result<GameStatus, GomokuError> {
+        self.with_game(session, |game| {
+            game.play(row, col)?;
+            Ok(GameStatus::of(game))
+        })
+    }
+
+    fn undo(&self, session: SessionId) -> Result<Option<MoveResult>, GomokuError> {
+        self.with_game(session, |game| Ok(game.undo().map(|(row, col)| MoveResult { row, col })))
+    }
+
+    fn ai_move(&self, ais: &Mutex<AiSessions>, session: SessionId, depth: usize) -> Result<AiMove, GomokuError> {
+        let game = self.with_game(session, |game| Ok(game.clone()))?;
+        check_not_over(&game)?;
+        let rules = Rules::of(&game);
+        let mut ai = ais.lock()?.take(rules);
+        let result = ai.find_move_scored(&game, depth);
+        let reply = result.map(|(mv, score)| (mv, AiMove::new(&ai, mv, score)));
+        ais.lock()?.put(rules, ai);
+        let (mv, reply) = reply.ok_or(GomokuError::NoMove)?;
+
+        self.with_game(session, |current| {
+            if current.moves() != game.moves() {
+                return Err(GomokuError::GameChanged);
+            }
+            Ok(current.play(mv.0, mv.1)?)
+        })?;
+        Ok(reply)
+    }
+}
+
+// Everything besides the stones that a search's scores depend on. Gravity
+// and wrap change which moves are legal and which lines exist, and the
+// symmetries that hashes are shared under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Rules {
+    rows: usize,
+    cols: usize,
+    allow_overline: bool,
+    renju: bool,
+    win_length: usize,
+    gravity: bool,
+    wrap: bool,
+}
+
+impl Rules {
+    fn of(game: &Game) -> Self {
+        Rules {
+            rows: game.rows(),
+            cols: game.cols(),
+            allow_overline: game.allow_overline(),
+            renju: game.renju_mode(),
+            win_length: game.win_length(),
+            gravity: game.gravity(),
+            wrap: game.wrap(),
+        }
+    }
+}
+
+// One AI per board size and rules, kept across get_ai_move calls so the
+// next move's search starts from this one's transposition table, killers
+// and history. Scores found under other rules never carry over.
+#[derive(Default)]
+struct AiSessions {
+    ais: HashMap<Rules, AI>,
+}
+
+impl AiSessions {
+    fn get(&mut self, rules: Rules) -> &mut AI {
+        self.ais.entry(rules).or_insert_with(|| AI::new(rules.rows, rules.cols))
+    }
+
+    // The AI taken out of the map, so it can search with the lock released.
+    // Another search under these rules meanwhile starts from a fresh AI, and
+    // the one put back last is kept.
+    fn take(&mut self, rules: Rules) -> AI {
+        self.ais.remove(&rules).unwrap_or_else(|| AI::new(rules.rows, rules.cols))
+    }
+
+    fn put(&mut self, rules: Rules, ai: AI) {
+        self.ais.insert(rules, ai);
+    }
+}
+
+fn build_game(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+) -> Result<Game, GomokuError> {
+    let mut game = Game::from_board(board, current_player)?;
+    let win_length = check_win_length(win_length, game.rows(), game.cols())?;
+    game.set_allow_overline(allow_overline.unwrap_or(true));
+    game.set_renju_mode(renju.unwrap_or(false));
+    game.set_win_length(win_length);
+    Ok(game)
+}
+
+// Win length, 5 by default. A line too long to fit anywhere could never
+// be won, and the evaluation has nothing to score under 3.
+fn check_win_length(win_length: Option<usize>, rows: usize, cols: usize) -> Result<usize, GomokuError> {
+    let win_length = win_length.unwrap_or(5);
+    let max = rows.max(cols);
+    if (3..=max).contains(&win_length) {
+        Ok(win_length)
+    } else {
+        Err(GomokuError::InvalidWinLength { win_length, max })
+    }
 }
 
-// Get AI move
+// A five already on the board means there's nothing left to search
+fn check_not_over(game: &Game) -> Result<(), GomokuError> {
+    match game.five_on_board() {
+        Some(winner) => Err(GomokuError::GameOver { winner }),
+        None => Ok(()),
+    }
+}
+
+// The full validate_board when asked for, otherwise just what the engine
+// needs to not panic
+fn check_input(board: &[Vec<i8>], current_player: i8, validate: Option<bool>) -> Result<(), GomokuError> {
+    let result = if validate.unwrap_or(false) {
+        ai_core::validate_board(board, current_player)
+    } else {
+        check_board_shape(board, current_player)
+    };
+    Ok(result?)
+}
+
+// Get AI move, reusing the AI from the last call on this board size
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 fn get_ai_move(
+    ais: tauri::State<Mutex<AiSessions>>,
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    depth: usize,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    threads: Option<usize>,
+    win_length: Option<usize>,
+    validate: Option<bool>,
+) -> Result<AiMove, GomokuError> {
+    check_input(&board, current_player, validate)?;
+    let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+    check_not_over(&game)?;
+    let rules = Rules::of(&game);
+    // Other session commands shouldn't wait on the search
+    let mut ai = ais.lock()?.take(rules);
+
+    let result = match threads {
+        Some(n) if n > 1 => ai.find_move_parallel(&game, depth, n),
+        _ => ai.find_move_scored(&game, depth),
+    };
+    let reply = match result {
+        Some((mv, score)) => Ok(AiMove::new(&ai, mv, score)),
+        None => Err(GomokuError::NoMove),
+    };
+    ais.lock()?.put(rules, ai);
+    reply
+}
+
+// The AI's `count` best moves, best first, for showing several variations
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_ai_moves_multi(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    depth: usize,
+    count: usize,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+    validate: Option<bool>,
+) -> Result<Vec<AiMove>, GomokuError> {
+    check_input(&board, current_player, validate)?;
+    let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+
+    let moves = ai.find_moves_multi(&game, depth, count);
+    Ok(moves.into_iter().map(|(mv, score)| AiMove::new(&ai, mv, score)).collect())
+}
+
+// get_ai_move for many unrelated positions under the same rules, one AI
+// per board size. Each position gets its own result, so a bad one doesn't
+// lose the rest.
+#[tauri::command]
+fn get_ai_moves_batch(
+    positions: Vec<(Vec<Vec<i8>>, i8)>,
+    depth: usize,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+) -> Vec<Result<AiMove, GomokuError>> {
+    let mut ais = AiSessions::default();
+    positions
+        .into_iter()
+        .map(|(board, current_player)| {
+            let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+            check_not_over(&game)?;
+            let ai = ais.get(Rules::of(&game));
+            ai.clear_tt();
+            match ai.find_move_scored(&game, depth) {
+                Some((mv, score)) => Ok(AiMove::new(ai, mv, score)),
+                None => Err(GomokuError::NoMove),
+            }
+        })
+        .collect()
+}
+
+// Get AI move along with node count, TT hits, depth reached and time
+#[tauri::command]
+fn get_ai_move_with_stats(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    depth: usize,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+    validate: Option<bool>,
+) -> Result<MoveStats, GomokuError> {
+    check_input(&board, current_player, validate)?;
+    let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+
+    match ai.find_move_with_stats(&game, depth) {
+        (Some((row, col)), stats) => Ok(MoveStats { row, col, stats }),
+        (None, _) => Err(GomokuError::NoMove),
+    }
+}
+
+// Get AI move at a named strength, see Difficulty for what each level means
+#[tauri::command]
+fn get_ai_move_by_difficulty(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    difficulty: Difficulty,
+    validate: Option<bool>,
+) -> Result<AiMove, GomokuError> {
+    check_input(&board, current_player, validate)?;
+    let game = Game::from_board(board, current_player)?;
+    check_not_over(&game)?;
+    let mut ai = AI::with_config(game.rows(), game.cols(), difficulty.config());
+
+    match ai.find_move_checked(&game, difficulty.depth()) {
+        Some((mv, score)) => Ok(AiMove::new(&ai, mv, score)),
+        None => Err(GomokuError::NoMove),
+    }
+}
+
+// A depth for get_ai_move that should take about `budget_ms` on a
+// size x size board
+#[tauri::command]
+fn suggest_depth(size: usize, budget_ms: u64) -> usize {
+    AI::suggest_depth(size, budget_ms)
+}
+
+// Get AI move within a time budget
+#[tauri::command]
+fn get_ai_move_timed(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    time_ms: u64,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+    validate: Option<bool>,
+) -> Result<AiMove, GomokuError> {
+    check_input(&board, current_player, validate)?;
+    let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+
+    match ai.find_move_timed(&game, time_ms) {
+        Some((mv, score)) => Ok(AiMove::new(&ai, mv, score)),
+        None => Err(GomokuError::NoMove),
+    }
+}
+
+// get_ai_move that emits search_progress after each completed depth and
+// can be stopped early with cancel_search. Runs off the main thread so the
+// cancel can get through.
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn get_ai_move_streaming(
+    window: tauri::Window,
+    cancel: tauri::State<SearchCancel>,
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    depth: usize,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+    validate: Option<bool>,
+) -> Result<AiMove, GomokuError> {
+    check_input(&board, current_player, validate)?;
+    let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+
+    cancel.0.store(false, Ordering::Relaxed);
+    ai.set_stop_flag(Arc::clone(&cancel.0));
+    let result = ai.find_move_with_progress(&game, depth, |progress| {
+        // Nobody listening is no reason to stop searching
+        let _ = window.emit("search_progress", progress);
+    });
+    match result {
+        Some((mv, score)) => Ok(AiMove::new(&ai, mv, score)),
+        None => Err(GomokuError::NoMove),
+    }
+}
+
+#[tauri::command]
+fn cancel_search(cancel: tauri::State<SearchCancel>) {
+    cancel.0.store(true, Ordering::Relaxed);
+}
+
+// Best move for `player`, e.g. a hint for the human, whoever the frontend
+// thinks is to move. `validate` also checks that `player` is to move.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_hint(
+    board: Vec<Vec<i8>>,
+    player: i8,
+    depth: usize,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+    validate: Option<bool>,
+) -> Result<AiMove, GomokuError> {
+    check_input(&board, player, validate)?;
+    let game = build_game(board, player, allow_overline, renju, win_length)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+
+    match ai.find_move_scored(&game, depth) {
+        Some((mv, score)) => Ok(AiMove::new(&ai, mv, score)),
+        None => Err(GomokuError::NoMove),
+    }
+}
+
+// The AI's reply if current_player tried (row, col), and the eval after
+// it, without playing anything
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn explore_move(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    row: usize,
+    col: usize,
+    depth: usize,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+    validate: Option<bool>,
+) -> Result<Exploration, GomokuError> {
+    check_input(&board, current_player, validate)?;
+    let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+    Ok(ai.explore_move(&game, (row, col), depth)?)
+}
+
+// Get the expected line of play
+#[tauri::command]
+fn get_pv(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    depth: usize,
+) -> Result<Vec<MoveResult>, GomokuError> {
+    let game = Game::from_board(board, current_player)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+
+    let best = ai.find_move(&game, depth).ok_or(GomokuError::NoMove)?;
+    let mut pv = ai.principal_variation(&game, depth);
+    if pv.is_empty() {
+        // Immediate win/block skips the search
+        pv.push(best);
+    }
+
+    Ok(pv.into_iter().map(|(row, col)| MoveResult { row, col }).collect())
+}
+
+// The search tree of get_ai_move, `trace_depth` plies deep, for debugging
+// move ordering and pruning. Empty when the move is forced.
+#[tauri::command]
+fn get_search_trace(
     board: Vec<Vec<i8>>,
     current_player: i8,
     depth: usize,
-) -> Result<MoveResult, String> {
-    let size = board.len();
-    let game = Game::from_board(board, current_player);
-    let mut ai = AI::new(size);
+    trace_depth: usize,
+) -> Result<Vec<TraceNode>, GomokuError> {
+    let game = Game::from_board(board, current_player)?;
+    check_not_over(&game)?;
+    let config = AIConfig { trace_depth, ..AIConfig::default() };
+    let mut ai = AI::with_config(game.rows(), game.cols(), config);
+
+    ai.find_move(&game, depth).ok_or(GomokuError::NoMove)?;
+    Ok(ai.trace().to_vec())
+}
+
+// Cells where current_player would make a five, four or open three
+#[tauri::command]
+fn get_threats(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+) -> Result<Vec<Threat>, GomokuError> {
+    let game = build_game(board, current_player, None, renju, win_length)?;
+    let ai = AI::new(game.rows(), game.cols());
+
+    Ok(ai.threat_cells(&game)
+        .into_iter()
+        .map(|(row, col, kind)| Threat { row, col, kind })
+        .collect())
+}
+
+// Every immediate win for current_player and for the opponent, for
+// highlighting, where find_move only acts on the first
+#[tauri::command]
+fn critical_squares(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    allow_overline: Option<bool>,
+    win_length: Option<usize>,
+) -> Result<CriticalSquares, GomokuError> {
+    let game = build_game(board, current_player, allow_overline, None, win_length)?;
+    Ok(game.critical_squares())
+}
+
+// Every open three `player` has on the board, with the cells that extend it
+#[tauri::command]
+fn find_open_threes(board: Vec<Vec<i8>>, player: i8, win_length: Option<usize>) -> Result<Vec<OpenThree>, GomokuError> {
+    let game = build_game(board, player, None, None, win_length)?;
+    Ok(game.open_threes(game.to_move()))
+}
+
+// Position strength without searching, cheap enough to call after every move
+#[tauri::command]
+fn evaluate_board(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    allow_overline: Option<bool>,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+) -> Result<Evaluation, GomokuError> {
+    let game = build_game(board, current_player, allow_overline, renju, win_length)?;
+    Ok(Evaluation {
+        score: game.evaluate(&EvalConfig::default()),
+        black: game.player_score(1),
+        white: game.player_score(2),
+    })
+}
 
-    match ai.find_move(&game, depth) {
-        Some((row, col)) => Ok(MoveResult { row, col }),
-        None => Err("No valid move found".to_string()),
+// Heuristic score of every candidate move, best first
+#[tauri::command]
+fn get_move_scores(
+    board: Vec<Vec<i8>>,
+    current_player: i8,
+    renju: Option<bool>,
+    win_length: Option<usize>,
+) -> Result<Vec<MoveScore>, GomokuError> {
+    let game = build_game(board, current_player, None, renju, win_length)?;
+    let ai = AI::new(game.rows(), game.cols());
+
+    Ok(ai.scored_moves(&game)
+        .into_iter()
+        .map(|((row, col), score)| MoveScore { row, col, score })
+        .collect())
+}
+
+// Let the AI make its Swap2 decision. White is to move after both the
+// three-stone opening and the two extra stones.
+#[tauri::command]
+fn swap2_decide(board: Vec<Vec<i8>>) -> Result<Swap2Decision, GomokuError> {
+    let game = Game::from_board(board, 2)?;
+    check_not_over(&game)?;
+    let mut ai = AI::new(game.rows(), game.cols());
+
+    let decision = match swap2_phase(&game) {
+        Swap2Phase::ChooseOption => match ai.swap2_choose_option(&game) {
+            Some(Swap2Option::Swap) => Swap2Decision::new("swap", &[]),
+            Some(Swap2Option::Stay { reply }) => Swap2Decision::new("stay", &[reply]),
+            Some(Swap2Option::PlaceTwo { black, white }) => Swap2Decision::new("place_two", &[black, white]),
+            None => return Err(GomokuError::NoMove),
+        },
+        Swap2Phase::ChooseColor => match ai.swap2_choose_color(&game) {
+            Some(Swap2Color::Black) => Swap2Decision::new("take_black", &[]),
+            Some(Swap2Color::White { reply }) => Swap2Decision::new("take_white", &[reply]),
+            None => return Err(GomokuError::NoMove),
+        },
+        _ => return Err(GomokuError::NotSwap2Decision),
+    };
+
+    Ok(decision)
+}
+
+// Serialize a game, moves oldest first, to a JSON string
+#[tauri::command]
+fn save_game(board: Vec<Vec<i8>>, current_player: i8, moves: Vec<(usize, usize)>) -> Result<String, GomokuError> {
+    let state = GameState {
+        rows: board.len(),
+        cols: board.first().map_or(0, |r| r.len()),
+        board,
+        current_player,
+        moves,
+    };
+    Game::from_state(&state).map_err(|message| GomokuError::InvalidSave { message })?;
+
+    serde_json::to_string(&state).map_err(|e| GomokuError::Internal { message: e.to_string() })
+}
+
+// Parse and check a game saved by save_game
+#[tauri::command]
+fn load_game(json: String) -> Result<GameState, GomokuError> {
+    let state: GameState = serde_json::from_str(&json).map_err(|e| GomokuError::InvalidSave { message: e.to_string() })?;
+    Ok(Game::from_state(&state).map_err(|message| GomokuError::InvalidSave { message })?.to_state())
+}
+
+// Reject boards the engine can't make sense of: bad dimensions or cells,
+// an impossible player to move, or a game that's already over
+#[tauri::command]
+fn validate_board(board: Vec<Vec<i8>>, current_player: i8) -> BoardCheck {
+    match ai_core::validate_board(&board, current_player) {
+        Ok(()) => BoardCheck { valid: true, reason: None, message: None },
+        Err(reason) => BoardCheck { valid: false, reason: Some(reason), message: Some(reason.to_string()) },
     }
 }
 
-// Check win condition
+// Stone counts, occupied cells and the player to move by parity, for
+// checking the current_player passed to other commands
 #[tauri::command]
-fn check_win(board: Vec<Vec<i8>>, row: usize, col: usize) -> Result<GameStatus, String> {
-    let size = board.len();
+fn board_info(board: Vec<Vec<i8>>) -> Result<BoardInfo, GomokuError> {
+    Ok(ai_core::board_info(&board)?)
+}
 
-    if row >= size || col >= size {
-        return Err("Invalid position".to_string());
+// Check win condition, or a draw on a full board
+// allow_overline defaults to true (freestyle); pass false for Renju-style exact five
+#[tauri::command]
+fn check_win(
+    board: Vec<Vec<i8>>,
+    row: usize,
+    col: usize,
+    allow_overline: Option<bool>,
+    win_length: Option<usize>,
+) -> Result<GameStatus, GomokuError> {
+    let rows = board.len();
+    let cols = board.first().map_or(0, |r| r.len());
+    let allow_overline = allow_overline.unwrap_or(true);
+
+    check_board_shape(&board, 1)?;
+    let win_length = check_win_length(win_length, rows, cols)?;
+    if row >= rows || col >= cols {
+        return Err(GomokuError::InvalidPosition { row, col });
     }
 
     let player = board[row][col];
@@ -54,35 +725,188 @@ fn check_win(board: Vec<Vec<i8>>, row: usize, col: usize) -> Result<GameStatus,
         return Ok(GameStatus {
             is_win: false,
             winner: None,
+            is_draw: false,
+            winning_line: None,
         });
     }
 
     let dirs = [(0, 1), (1, 0), (1, 1), (1, -1)];
 
     for &(dr, dc) in &dirs {
-        let count = 1
-            + count_dir(&board, size, row, col, dr, dc, player)
-            + count_dir(&board, size, row, col, -dr, -dc, player);
+        let back = count_dir(&board, row, col, -dr, -dc, player);
+        let count = 1 + count_dir(&board, row, col, dr, dc, player) + back;
 
-        if count >= 5 {
+        if count == win_length || (allow_overline && count > win_length) {
+            let (start_r, start_c) = (row as i32 - dr * back as i32, col as i32 - dc * back as i32);
+            let line = (0..count as i32).map(|k| ((start_r + dr * k) as usize, (start_c + dc * k) as usize));
             return Ok(GameStatus {
                 is_win: true,
                 winner: Some(player),
+                is_draw: false,
+                winning_line: Some(line.collect()),
             });
         }
     }
 
+    // No five: the game is drawn once there's nowhere left to play
     Ok(GameStatus {
         is_win: false,
         winner: None,
+        is_draw: board.iter().flatten().all(|&cell| cell != 0),
+        winning_line: None,
+    })
+}
+
+// Would `player` make five by playing (row, col)? Cheap enough for hover
+// hints, no search is run
+#[tauri::command]
+fn is_winning_move(
+    board: Vec<Vec<i8>>,
+    row: usize,
+    col: usize,
+    player: i8,
+    allow_overline: Option<bool>,
+    win_length: Option<usize>,
+) -> Result<bool, GomokuError> {
+    let game = build_game(board, player, allow_overline, None, win_length)?;
+    Ok(game.is_winning_move(row, col, game.to_move())?)
+}
+
+// Random size x size puzzle won in at most `win_in` moves, see
+// AI::generate_puzzle. The same seed gives the same puzzle.
+#[tauri::command]
+fn generate_puzzle(size: usize, win_in: usize, seed: Option<u64>, show_solution: Option<bool>) -> Result<Puzzle, GomokuError> {
+    let config = AIConfig { seed, ..Default::default() };
+    let (game, (row, col)) = AI::with_config(size, size, config).generate_puzzle(size, win_in).ok_or(GomokuError::NoPuzzle)?;
+
+    let state = game.to_state();
+    Ok(Puzzle {
+        board: state.board,
+        current_player: state.current_player,
+        solution: show_solution.unwrap_or(false).then_some(MoveResult { row, col }),
+    })
+}
+
+// Evals and blunders for each move of a game played from an empty board
+#[tauri::command]
+fn analyze_game(moves: Vec<(usize, usize)>, size: Option<usize>) -> Result<Vec<MoveAnalysis>, GomokuError> {
+    let size = size.unwrap_or(15);
+    if size == 0 {
+        return Err(GomokuError::InvalidSize);
+    }
+    let mut ai = AI::new(size, size);
+    Ok(ai.analyze_game(&moves, size))
+}
+
+// Start a server-side game on an empty size x size board, Black to move.
+// With gravity, stones drop down their column as in Connect-4; with wrap,
+// lines run on across the edges. The get_ai_move AI for these rules
+// forgets the last game.
+#[tauri::command]
+fn new_game(
+    sessions: tauri::State<Sessions>,
+    ais: tauri::State<Mutex<AiSessions>>,
+    size: usize,
+    gravity: Option<bool>,
+    wrap: Option<bool>,
+) -> Result<SessionId, GomokuError> {
+    let mut game = Game::from_board(vec![vec![0; size]; size], 1)?;
+    game.set_gravity(gravity.unwrap_or(false));
+    game.set_wrap(wrap.unwrap_or(false));
+    start_session(&sessions, &ais, game)
+}
+
+// new_game with Black's handicap stones on the board, White to move
+#[tauri::command]
+fn new_game_with_handicap(
+    sessions: tauri::State<Sessions>,
+    ais: tauri::State<Mutex<AiSessions>>,
+    size: usize,
+    handicap_cells: Vec<(usize, usize)>,
+) -> Result<SessionId, GomokuError> {
+    let game = Game::with_handicap(size, &handicap_cells)?;
+    start_session(&sessions, &ais, game)
+}
+
+fn start_session(sessions: &Sessions, ais: &Mutex<AiSessions>, game: Game) -> Result<SessionId, GomokuError> {
+    if let Some(ai) = ais.lock()?.ais.get_mut(&Rules::of(&game)) {
+        ai.clear_tt();
+    }
+    let id = sessions.next_id.fetch_add(1, Ordering::Relaxed);
+    sessions.games.lock()?.insert(id, game);
+    Ok(id)
+}
+
+#[tauri::command]
+fn end_game(sessions: tauri::State<Sessions>, session: SessionId) -> Result<(), GomokuError> {
+    sessions.games.lock()?.remove(&session);
+    Ok(())
+}
+
+#[tauri::command]
+fn session_state(sessions: tauri::State<Sessions>, session: SessionId) -> Result<GameState, GomokuError> {
+    sessions.with_game(session, |game| Ok(game.to_state()))
+}
+
+// Play for the side to move
+#[tauri::command]
+fn play_move(sessions: tauri::State<Sessions>, session: SessionId, row: usize, col: usize) -> Result<GameStatus, GomokuError> {
+    sessions.play(session, row, col)
+}
+
+// Gravity play: drop the side to move's stone in `col`
+#[tauri::command]
+fn drop_stone(sessions: tauri::State<Sessions>, session: SessionId, col: usize) -> Result<DropResult, GomokuError> {
+    sessions.with_game(session, |game| {
+        let row = game.drop_stone(col)?;
+        Ok(DropResult { row, status: GameStatus::of(game) })
     })
 }
 
-fn count_dir(board: &Vec<Vec<i8>>, size: usize, row: usize, col: usize, dr: i32, dc: i32, player: i8) -> usize {
+// Take back the last move, if any
+#[tauri::command]
+fn undo(sessions: tauri::State<Sessions>, session: SessionId) -> Result<Option<MoveResult>, GomokuError> {
+    sessions.undo(session)
+}
+
+// Search and play the AI's move for the side to move, with the
+// get_ai_move AI for the board size. No lock is held while searching; the
+// move is rejected if the game changed meanwhile.
+#[tauri::command]
+fn ai_move(sessions: tauri::State<Sessions>, ais: tauri::State<Mutex<AiSessions>>, session: SessionId, depth: usize) -> Result<AiMove, GomokuError> {
+    sessions.ai_move(&ais, session, depth)
+}
+
+// Would Black be forbidden from playing here under Renju rules?
+#[tauri::command]
+fn check_forbidden(board: Vec<Vec<i8>>, row: usize, col: usize) -> Result<bool, GomokuError> {
+    let game = Game::from_board(board, 1)?;
+
+    if row >= game.rows() || col >= game.cols() {
+        return Err(GomokuError::InvalidPosition { row, col });
+    }
+
+    Ok(game.is_forbidden(row, col))
+}
+
+// Which Renju rule Black would break here, and the lines that break it
+#[tauri::command]
+fn forbidden_reason(board: Vec<Vec<i8>>, row: usize, col: usize) -> Result<Option<Forbidden>, GomokuError> {
+    let game = Game::from_board(board, 1)?;
+
+    if row >= game.rows() || col >= game.cols() {
+        return Err(GomokuError::InvalidPosition { row, col });
+    }
+
+    Ok(game.forbidden_reason(row, col))
+}
+
+fn count_dir(board: &[Vec<i8>], row: usize, col: usize, dr: i32, dc: i32, player: i8) -> usize {
+    let (rows, cols) = (board.len() as i32, board[0].len() as i32);
     let mut count = 0;
     let (mut r, mut c) = (row as i32 + dr, col as i32 + dc);
 
-    while r >= 0 && r < size as i32 && c >= 0 && c < size as i32 && board[r as usize][c as usize] == player {
+    while r >= 0 && r < rows && c >= 0 && c < cols && board[r as usize][c as usize] == player {
         count += 1;
         r += dr;
         c += dc;
@@ -93,7 +917,292 @@ fn count_dir(board: &Vec<Vec<i8>>, size: usize, row: usize, col: usize, dr: i32,
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![get_ai_move, check_win])
+        .manage(Sessions::default())
+        .manage(SearchCancel::default())
+        .manage(Mutex::new(AiSessions::default()))
+        .invoke_handler(tauri::generate_handler![get_ai_move, get_ai_moves_multi, get_ai_moves_batch, get_ai_move_by_difficulty, get_ai_move_with_stats, get_ai_move_timed, suggest_depth, get_ai_move_streaming, cancel_search, get_hint, explore_move, get_pv, get_search_trace, get_threats, critical_squares, find_open_threes, get_move_scores, evaluate_board, check_win, is_winning_move, check_forbidden, forbidden_reason, swap2_decide, save_game, load_game, validate_board, board_info, analyze_game, generate_puzzle, new_game, new_game_with_handicap, end_game, session_state, play_move, drop_stone, undo, ai_move])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gomoku_tauri::ai_core::MoveError;
+
+    fn new_session(sessions: &Sessions) -> SessionId {
+        let game = Game::from_board(vec![vec![0; 15]; 15], 1).unwrap();
+        start_session(sessions, &Mutex::new(AiSessions::default()), game).unwrap()
+    }
+
+    #[test]
+    fn play_and_undo_round_trip() {
+        let sessions = Sessions::default();
+        let id = new_session(&sessions);
+        let moves = [(7, 7), (7, 8), (8, 8)];
+
+        for (row, col) in moves {
+            let status = sessions.play(id, row, col).unwrap();
+            assert!(!status.is_win && !status.is_draw);
+        }
+        let state = sessions.with_game(id, |game| Ok(game.to_state())).unwrap();
+        assert_eq!(state.moves, moves);
+        assert_eq!(state.current_player, 2);
+
+        for &(row, col) in moves.iter().rev() {
+            let undone = sessions.undo(id).unwrap().unwrap();
+            assert_eq!((undone.row, undone.col), (row, col));
+        }
+        assert!(sessions.undo(id).unwrap().is_none());
+        let state = sessions.with_game(id, |game| Ok(game.to_state())).unwrap();
+        assert_eq!(state.board, vec![vec![0; 15]; 15]);
+        assert_eq!(state.current_player, 1);
+    }
+
+    #[test]
+    fn illegal_moves_come_back_as_errors() {
+        let sessions = Sessions::default();
+        let id = new_session(&sessions);
+        sessions.play(id, 7, 7).unwrap();
+
+        let illegal = |reason| Err(GomokuError::IllegalMove { reason });
+        assert_eq!(sessions.play(id, 7, 7).map(|_| ()), illegal(MoveError::Occupied));
+        assert_eq!(sessions.play(id, 15, 0).map(|_| ()), illegal(MoveError::OutOfBounds));
+        assert_eq!(sessions.play(id + 1, 0, 0).map(|_| ()), Err(GomokuError::UnknownSession));
+        // Nothing was played by the failed moves
+        assert_eq!(sessions.with_game(id, |game| Ok(game.moves().len())), Ok(1));
+    }
+
+    #[test]
+    fn taken_ai_comes_back_with_its_table() {
+        let mut ais = AiSessions::default();
+        let mut board = vec![vec![0; 15]; 15];
+        for (row, col, player) in [(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2), (9, 6, 1), (8, 5, 2)] {
+            board[row][col] = player;
+        }
+        let game = Game::from_board(board, 1).unwrap();
+        let rules = Rules::of(&game);
+
+        let mut ai = ais.take(rules);
+        assert!(ais.ais.is_empty());
+        ai.find_move(&game, 3).unwrap();
+        ais.put(rules, ai);
+
+        // The same search again starts from the table the first one left
+        let (_, stats) = ais.get(rules).find_move_with_stats(&game, 3);
+        assert!(stats.tt_hits > 0);
+
+        // A wrapped board of the same size gets an AI of its own, and the
+        // plain board's keeps its table
+        let mut wrapped = game.clone();
+        wrapped.set_wrap(true);
+        assert_ne!(Rules::of(&wrapped), rules);
+        ais.get(Rules::of(&wrapped)).find_move(&wrapped, 2).unwrap();
+        assert_eq!(ais.ais.len(), 2);
+        let (_, stats) = ais.get(rules).find_move_with_stats(&game, 3);
+        assert!(stats.tt_hits > 0);
+    }
+
+    #[test]
+    fn stray_cell_values_are_rejected_before_searching() {
+        let mut board = vec![vec![0; 15]; 15];
+        board[2][9] = 7;
+        let invalid = GomokuError::InvalidBoard { reason: BoardError::InvalidCell { row: 2, col: 9, value: 7 } };
+        assert_eq!(check_input(&board, 1, None), Err(invalid.clone()));
+        assert_eq!(check_input(&board, 1, Some(true)), Err(invalid.clone()));
+        assert_eq!(build_game(board, 1, None, None, None).err(), Some(invalid));
+    }
+
+    fn midgame() -> Vec<Vec<i8>> {
+        let mut board = vec![vec![0; 15]; 15];
+        for (row, col, player) in [(7, 7, 1), (7, 8, 2), (8, 8, 1), (6, 6, 2), (9, 6, 1), (8, 5, 2)] {
+            board[row][col] = player;
+        }
+        board
+    }
+
+    #[test]
+    fn hint_is_the_move_the_ai_would_play() {
+        let board = midgame();
+        let game = Game::from_board(board.clone(), 1).unwrap();
+        let (mv, score) = AI::new(15, 15).find_move_scored(&game, 3).unwrap();
+        let hint = get_hint(board.clone(), 1, 3, None, None, None, Some(true)).unwrap();
+        assert_eq!(((hint.row, hint.col), hint.score), (mv, score));
+
+        let invalid = |reason| Err(GomokuError::InvalidBoard { reason });
+        assert_eq!(get_hint(board.clone(), 3, 3, None, None, None, None).map(|_| ()), invalid(BoardError::InvalidPlayer));
+        // Black is to move, so White's hint only passes unvalidated
+        assert!(get_hint(board.clone(), 2, 3, None, None, None, None).is_ok());
+        assert_eq!(get_hint(board, 2, 3, None, None, None, Some(true)).map(|_| ()), invalid(BoardError::StoneImbalance));
+    }
+
+    #[test]
+    fn win_length_must_fit_the_board() {
+        let board = || vec![vec![0; 8]; 15];
+        for win_length in [3, 4, 6, 15] {
+            assert_eq!(build_game(board(), 1, None, None, Some(win_length)).unwrap().win_length(), win_length);
+        }
+        for win_length in [0, 2, 16] {
+            let err = build_game(board(), 1, None, None, Some(win_length)).err();
+            assert_eq!(err, Some(GomokuError::InvalidWinLength { win_length, max: 15 }));
+        }
+        assert_eq!(check_win(board(), 0, 0, None, Some(16)).map(|_| ()), Err(GomokuError::InvalidWinLength { win_length: 16, max: 15 }));
+    }
+
+    // validate_board's rejection, which check_input with validation passes on
+    fn rejection(board: Vec<Vec<i8>>, current_player: i8) -> Option<BoardError> {
+        let check = validate_board(board.clone(), current_player);
+        assert_eq!(check.valid, check.reason.is_none());
+        assert_eq!(check_input(&board, current_player, Some(true)).err(), check.reason.map(|reason| GomokuError::InvalidBoard { reason }));
+        check.reason
+    }
+
+    #[test]
+    fn ragged_boards_are_rejected() {
+        let mut board = vec![vec![0; 15]; 15];
+        board[4].pop();
+        assert_eq!(rejection(board.clone(), 1), Some(BoardError::Ragged));
+        assert_eq!(check_input(&board, 1, None), Err(GomokuError::InvalidBoard { reason: BoardError::Ragged }));
+        assert_eq!(rejection(Vec::new(), 1), Some(BoardError::Empty));
+    }
+
+    #[test]
+    fn bad_cells_are_rejected() {
+        let mut board = vec![vec![0; 15]; 15];
+        board[0][14] = -2;
+        assert_eq!(rejection(board, 1), Some(BoardError::InvalidCell { row: 0, col: 14, value: -2 }));
+    }
+
+    #[test]
+    fn bad_players_are_rejected() {
+        for player in [0, 3, -1] {
+            assert_eq!(rejection(vec![vec![0; 15]; 15], player), Some(BoardError::InvalidPlayer));
+            assert!(check_input(&vec![vec![0; 15]; 15], player, None).is_err());
+        }
+    }
+
+    #[test]
+    fn impossible_stone_counts_are_rejected() {
+        let mut board = vec![vec![0; 15]; 15];
+        board[7][7] = 1;
+        board[7][8] = 1;
+        // Two black stones and no white
+        assert_eq!(rejection(board.clone(), 2), Some(BoardError::StoneImbalance));
+        board[7][9] = 2;
+        // Balanced, but it's White's turn, not Black's
+        assert_eq!(rejection(board.clone(), 1), Some(BoardError::StoneImbalance));
+        assert_eq!(rejection(board.clone(), 2), None);
+        // Shape alone doesn't look at counts
+        assert_eq!(check_input(&board, 1, None), Ok(()));
+    }
+
+    #[test]
+    fn ai_moves_play_and_undo_in_a_session() {
+        let sessions = Sessions::default();
+        let ais = Mutex::new(AiSessions::default());
+        let id = start_session(&sessions, &ais, Game::from_board(vec![vec![0; 15]; 15], 1).unwrap()).unwrap();
+
+        // The AI opens, the human answers, the AI again
+        let first = sessions.ai_move(&ais, id, 2).unwrap();
+        assert_eq!((first.row, first.col), (7, 7));
+        sessions.play(id, 7, 8).unwrap();
+        let second = sessions.ai_move(&ais, id, 2).unwrap();
+        let moves = sessions.with_game(id, |game| Ok(game.moves().to_vec())).unwrap();
+        assert_eq!(moves, [(7, 7), (7, 8), (second.row, second.col)]);
+
+        // Both searches were the shared 15x15 AI's, which was put back
+        let rules = sessions.with_game(id, |game| Ok(Rules::of(game))).unwrap();
+        assert!(ais.lock().unwrap().ais.contains_key(&rules));
+
+        // Taking back the AI's move and the human's leaves the AI to move again
+        for (row, col) in [(second.row, second.col), (7, 8)] {
+            let undone = sessions.undo(id).unwrap().unwrap();
+            assert_eq!((undone.row, undone.col), (row, col));
+        }
+        let state = sessions.with_game(id, |game| Ok(game.to_state())).unwrap();
+        assert_eq!((state.moves, state.current_player), (vec![(7, 7)], 2));
+        let again = sessions.ai_move(&ais, id, 2).unwrap();
+        assert_eq!(sessions.with_game(id, |game| Ok(game.last_move())), Ok(Some((again.row, again.col))));
+
+        assert_eq!(sessions.ai_move(&ais, id + 1, 2).map(|_| ()), Err(GomokuError::UnknownSession));
+    }
+
+    #[test]
+    fn ai_move_stops_once_the_game_is_won() {
+        let sessions = Sessions::default();
+        let ais = Mutex::new(AiSessions::default());
+        let id = new_session(&sessions);
+        for (row, col) in [(7, 3), (0, 0), (7, 4), (0, 2), (7, 5), (0, 4), (7, 6), (0, 6)] {
+            sessions.play(id, row, col).unwrap();
+        }
+        // Black's four is a move from five, which the AI finds and plays
+        let win = sessions.ai_move(&ais, id, 2).unwrap();
+        assert!(win.forced_win);
+        let status = sessions.with_game(id, |game| Ok(GameStatus::of(game))).unwrap();
+        assert_eq!(status.winner, Some(1));
+        assert_eq!(sessions.ai_move(&ais, id, 2).map(|_| ()), Err(GomokuError::GameOver { winner: 1 }));
+    }
+
+    #[test]
+    fn board_info_counts_an_imbalanced_board() {
+        let mut board = vec![vec![0; 15]; 15];
+        for (row, col, player) in [(7, 7, 1), (7, 8, 1), (8, 8, 1), (0, 3, 2)] {
+            board[row][col] = player;
+        }
+        let info = board_info(board.clone()).unwrap();
+        assert_eq!((info.black, info.white, info.total), (3, 1, 4));
+        assert_eq!(info.occupied, vec![(0, 3), (7, 7), (7, 8), (8, 8)]);
+        // Four stones make it Black's turn by parity, but no game gets here
+        assert_eq!(info.to_move, 1);
+        assert!(!info.balanced);
+        assert_eq!(rejection(board.clone(), 1), Some(BoardError::StoneImbalance));
+
+        // White ahead is just as wrong
+        board[7][7] = 2;
+        board[7][8] = 2;
+        let info = board_info(board).unwrap();
+        assert_eq!((info.black, info.white, info.to_move, info.balanced), (1, 3, 1, false));
+        assert_eq!(board_info(vec![]), Err(GomokuError::InvalidBoard { reason: BoardError::Empty }));
+    }
+
+    #[test]
+    fn batch_matches_single_calls_and_keeps_going_past_a_bad_position() {
+        let board = |size: usize, stones: &[(usize, usize, i8)]| {
+            let mut board = vec![vec![0; size]; size];
+            for &(row, col, player) in stones {
+                board[row][col] = player;
+            }
+            board
+        };
+        let five = [(0, 0, 1), (0, 1, 1), (0, 2, 1), (0, 3, 1), (0, 4, 1), (5, 0, 2), (5, 2, 2), (5, 4, 2), (5, 6, 2)];
+        let positions = vec![
+            (board(15, &[(7, 7, 1), (7, 8, 2), (8, 8, 1)]), 2),
+            (vec![vec![0; 15], vec![0; 14]], 1),
+            (board(9, &[(4, 4, 1), (4, 5, 2)]), 1),
+            (board(15, &five), 2),
+            (board(15, &[(3, 3, 1), (4, 4, 2), (3, 4, 1), (5, 5, 2)]), 1),
+        ];
+
+        let batch = get_ai_moves_batch(positions.clone(), 3, None, None, None);
+        assert_eq!(batch.len(), positions.len());
+        assert_eq!(batch[1].as_ref().err(), Some(&GomokuError::InvalidBoard { reason: BoardError::Ragged }));
+        assert_eq!(batch[3].as_ref().err(), Some(&GomokuError::GameOver { winner: 1 }));
+        assert!(batch[4].is_ok());
+        for ((board, current_player), result) in positions.into_iter().zip(batch) {
+            // What a call for this position alone, on a fresh AI, gives
+            let single = build_game(board, current_player, None, None, None).and_then(|game| {
+                check_not_over(&game)?;
+                let mut ai = AI::new(game.rows(), game.cols());
+                let (mv, score) = ai.find_move_scored(&game, 3).ok_or(GomokuError::NoMove)?;
+                Ok(AiMove::new(&ai, mv, score))
+            });
+            match (result, single) {
+                (Ok(a), Ok(b)) => assert_eq!(
+                    (a.row, a.col, a.score, a.forced_win, a.forced_loss, a.resign, a.stability),
+                    (b.row, b.col, b.score, b.forced_win, b.forced_loss, b.resign, b.stability)
+                ),
+                (a, b) => assert_eq!(a.err(), b.err()),
+            }
+        }
+    }
+}